@@ -0,0 +1,101 @@
+//! Compares the `fast-sync` and `std-sync` modes on the hot paths that lock a
+//! mutex or hash a key: policies over the in-memory storage, and the keyed
+//! limiter shared between threads.
+//!
+//! Run it once per mode and compare the numbers:
+//!
+//! ```text
+//! cargo bench --bench sync
+//! cargo bench --bench sync --features std-sync
+//! ```
+//!
+//! Each case prints the best of five runs, as the time per operation.
+
+use sf_rate_limiter::policy::{FixedWindowPolicy, Policy};
+use sf_rate_limiter::simple;
+use sf_rate_limiter::storage::InMemoryStorage;
+use sf_rate_limiter::Duration;
+use std::hint::black_box;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+const KEYS: usize = 10_000;
+const ROUNDS: usize = 20;
+const THREADS: usize = 4;
+
+fn main() {
+    let mode = if cfg!(all(feature = "fast-sync", not(feature = "std-sync"))) {
+        "fast-sync"
+    } else {
+        "std-sync"
+    };
+    println!("mode: {mode}");
+
+    let keys: Vec<String> = (0..KEYS).map(|key| format!("client:{key}")).collect();
+
+    bench(
+        "in-memory storage, fixed window consume",
+        KEYS * ROUNDS,
+        || {
+            let mut storage = InMemoryStorage::new();
+
+            for _ in 0..ROUNDS {
+                for key in &keys {
+                    let mut policy = FixedWindowPolicy::new(
+                        100,
+                        key.clone(),
+                        Duration::minutes(1),
+                        &mut storage,
+                    )
+                    .unwrap();
+                    black_box(policy.consume(1).unwrap());
+                }
+            }
+        },
+    );
+
+    bench(
+        "keyed limiter, consume from 4 threads",
+        KEYS * ROUNDS * THREADS,
+        || {
+            let limiter = Arc::new(simple::per_minute(1_000).keyed());
+
+            let handles: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    let limiter = Arc::clone(&limiter);
+                    let keys = keys.clone();
+
+                    thread::spawn(move || {
+                        for _ in 0..ROUNDS {
+                            for key in &keys {
+                                black_box(limiter.consume(key, 1).unwrap());
+                            }
+                        }
+                    })
+                })
+                .collect();
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        },
+    );
+}
+
+/// Runs `run` five times and prints the best time per operation.
+fn bench<F: FnMut()>(name: &str, operations: usize, mut run: F) {
+    let best = (0..5)
+        .map(|_| {
+            let started = Instant::now();
+            run();
+            started.elapsed()
+        })
+        .min()
+        .unwrap();
+
+    println!(
+        "{name}: {:.0} ns/op",
+        best.as_nanos() as f64 / operations as f64
+    );
+}
@@ -0,0 +1,34 @@
+//! Paces outbound calls to a rate-sensitive third party with a leaky bucket.
+//!
+//! Every call waits for the time to act of its reservation, so the calls go out
+//! evenly spaced instead of in a burst. Run with `cargo run --example paced_client`.
+
+use sf_rate_limiter::policy::{LeakyBucketPolicy, Policy, Rate};
+use sf_rate_limiter::storage::InMemoryStorage;
+use sf_rate_limiter::Duration;
+use std::thread;
+use std::time::Instant;
+
+fn main() {
+    let mut storage = InMemoryStorage::new();
+    let mut policy = LeakyBucketPolicy::new(
+        10,
+        "third-party-api".into(),
+        Rate::new(Duration::milliseconds(100), 1),
+        &mut storage,
+    )
+    .unwrap();
+
+    let started = Instant::now();
+
+    for call in 1..=5 {
+        let reservation = policy.reserve(1, None).unwrap();
+        let wait = *reservation.get_time_to_act() - chrono::Local::now();
+
+        if let Ok(wait) = wait.to_std() {
+            thread::sleep(wait);
+        }
+
+        println!("call #{call} sent after {:?}", started.elapsed());
+    }
+}
@@ -0,0 +1,43 @@
+//! Limits simulated API requests per client IP with the thread-safe keyed limiter.
+//!
+//! Run with `cargo run --example per_ip_limits`.
+
+use sf_rate_limiter::simple;
+use std::sync::Arc;
+use std::thread;
+
+fn main() {
+    let limiter = Arc::new(simple::per_minute(3).keyed());
+    let clients = ["10.0.0.1", "10.0.0.2"];
+
+    let handles: Vec<_> = clients
+        .iter()
+        .map(|ip| {
+            let limiter = Arc::clone(&limiter);
+            let ip = ip.to_string();
+
+            thread::spawn(move || {
+                for request in 1..=5 {
+                    let rate_limit = limiter.consume(&ip, 1).unwrap();
+
+                    if rate_limit.is_accepted() {
+                        println!(
+                            "{ip} request #{request}: 200 OK, {} left",
+                            rate_limit.get_remaining_tokens()
+                        );
+                    } else {
+                        let retry_after = rate_limit.get_retry_after() - chrono::Local::now();
+                        println!(
+                            "{ip} request #{request}: 429 Too Many Requests, retry in {}s",
+                            (retry_after.num_milliseconds().max(0) + 999) / 1000
+                        );
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
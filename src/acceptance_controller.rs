@@ -0,0 +1,245 @@
+use crate::error::PolicyError;
+use crate::{ChronoTimestampMillis, Clock, Duration, RateLimit, SystemClock};
+
+/// A limit change proposed by an [`AcceptanceController`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimitAdjustment {
+    pub previous_limit: u64,
+    pub limit: u64,
+    /// Share of the observed requests that were accepted, between `0.0` and `1.0`.
+    pub acceptance_ratio: f64,
+}
+
+/// Watches the acceptance ratio of a limiter and proposes new limits to steer it
+/// towards a target, within guardrails.
+///
+/// Feed it every decision with [`Self::observe()`] and call [`Self::adjust()`]
+/// with the current limit. Once per observation window it raises the limit when
+/// fewer requests than targeted are accepted and lowers it when more are, always
+/// within `min_limit..=max_limit`, and returns the change as a [`LimitAdjustment`]
+/// to apply. Every adjustment is also passed to the hook set with
+/// [`Self::with_adjust_hook()`], e.g. to record it. The controller never changes
+/// a policy by itself.
+///
+/// The change is PID-like: the gap between the target and the observed ratio is
+/// weighted by a proportional gain, the sum of the gaps of all windows by an
+/// integral gain and the change of the gap since the last window by a
+/// derivative gain. Only the proportional gain is set by default.
+pub struct AcceptanceController {
+    target_ratio: f64,
+    min_limit: u64,
+    max_limit: u64,
+    window: ChronoTimestampMillis,
+    gain: f64,
+    integral_gain: f64,
+    derivative_gain: f64,
+    error_sum: f64,
+    previous_error: Option<f64>,
+    window_start: Option<ChronoTimestampMillis>,
+    accepted: u64,
+    observed: u64,
+    on_adjust: Option<AdjustHook>,
+    clock: Box<dyn Clock>,
+}
+
+/// Called with every limit change an [`AcceptanceController`] proposes.
+pub type AdjustHook = Box<dyn FnMut(&LimitAdjustment) + Send>;
+
+impl AcceptanceController {
+    /// `target_ratio` is the share of requests to accept, between `0.0` and `1.0`,
+    /// evaluated over every `window`.
+    pub fn new(
+        target_ratio: f64,
+        min_limit: u64,
+        max_limit: u64,
+        window: Duration,
+    ) -> Result<Self, PolicyError> {
+        if !(0. ..=1.).contains(&target_ratio) {
+            return Err(PolicyError::InvalidFractionError);
+        }
+
+        if min_limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if min_limit > max_limit {
+            return Err(PolicyError::InvalidLimitRangeError);
+        }
+
+        Ok(Self {
+            target_ratio,
+            min_limit,
+            max_limit,
+            window: window.num_milliseconds(),
+            gain: 1.,
+            integral_gain: 0.,
+            derivative_gain: 0.,
+            error_sum: 0.,
+            previous_error: None,
+            window_start: None,
+            accepted: 0,
+            observed: 0,
+            on_adjust: None,
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Sets how strongly the limit reacts to the gap between the observed and the
+    /// target ratio, `1.0` by default: a gap of 10% changes the limit by 10%.
+    pub fn with_gain(mut self, gain: f64) -> Self {
+        self.gain = gain.max(0.);
+        self
+    }
+
+    /// Sets how strongly the limit reacts to the sum of the gaps of every window so
+    /// far, `0.0` by default, so a gap that persists keeps moving the limit.
+    pub fn with_integral_gain(mut self, integral_gain: f64) -> Self {
+        self.integral_gain = integral_gain.max(0.);
+        self
+    }
+
+    /// Sets how strongly the limit reacts to the change of the gap since the
+    /// previous window, `0.0` by default, to damp overshooting.
+    pub fn with_derivative_gain(mut self, derivative_gain: f64) -> Self {
+        self.derivative_gain = derivative_gain.max(0.);
+        self
+    }
+
+    /// Calls `on_adjust` with every adjustment [`Self::adjust()`] returns.
+    pub fn with_adjust_hook<F: FnMut(&LimitAdjustment) + Send + 'static>(
+        mut self,
+        on_adjust: F,
+    ) -> Self {
+        self.on_adjust = Some(Box::new(on_adjust));
+        self
+    }
+
+    /// Replaces the clock the observation windows are measured with.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Records the decision of a limiter.
+    pub fn observe(&mut self, rate_limit: &RateLimit) {
+        self.window_start
+            .get_or_insert_with(|| self.clock.now().timestamp_millis());
+        self.observed = self.observed.saturating_add(1);
+
+        if rate_limit.is_accepted() {
+            self.accepted = self.accepted.saturating_add(1);
+        }
+    }
+
+    /// Proposes a new limit once the observation window has passed, and starts the next window.
+    /// Returns `None` while the window is running or when the limit should not change.
+    pub fn adjust(&mut self, current_limit: u64) -> Option<LimitAdjustment> {
+        let now = self.clock.now().timestamp_millis();
+        let window_start = self.window_start?;
+
+        if now - window_start < self.window || self.observed == 0 {
+            return None;
+        }
+
+        let acceptance_ratio = self.accepted as f64 / self.observed as f64;
+        let error = self.target_ratio - acceptance_ratio;
+        self.error_sum += error;
+        let change = error - self.previous_error.unwrap_or(error);
+        self.previous_error = Some(error);
+
+        let factor = 1.
+            + self.gain * error
+            + self.integral_gain * self.error_sum
+            + self.derivative_gain * change;
+        let limit =
+            ((current_limit as f64 * factor).round() as u64).clamp(self.min_limit, self.max_limit);
+
+        self.window_start = Some(now);
+        self.accepted = 0;
+        self.observed = 0;
+
+        if limit == current_limit {
+            return None;
+        }
+
+        let adjustment = LimitAdjustment {
+            previous_limit: current_limit,
+            limit,
+            acceptance_ratio,
+        };
+
+        if let Some(on_adjust) = &mut self.on_adjust {
+            on_adjust(&adjustment);
+        }
+
+        Some(adjustment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FixedWindowPolicy, Policy};
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn raises_the_limit_when_too_many_requests_are_rejected() {
+        let clock = MockClock::default();
+        let mut controller = AcceptanceController::new(0.9, 5, 15, Duration::minutes(1))
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        for _ in 0..20 {
+            controller.observe(&policy.consume(1).unwrap().rate_limit);
+        }
+        assert_eq!(controller.adjust(10), None);
+
+        clock.advance(Duration::minutes(1));
+        let adjustment = controller.adjust(10).unwrap();
+        assert_eq!(adjustment.acceptance_ratio, 0.5);
+        assert_eq!(adjustment.limit, 14);
+
+        clock.advance(Duration::minutes(1));
+        assert_eq!(controller.adjust(14), None);
+    }
+
+    #[test]
+    fn integral_gain_keeps_raising_a_persistently_low_ratio() {
+        let clock = MockClock::default();
+        let adjustments = Arc::new(Mutex::new(Vec::new()));
+        let mut controller = AcceptanceController::new(0.9, 5, 100, Duration::minutes(1))
+            .unwrap()
+            .with_gain(0.)
+            .with_integral_gain(1.)
+            .with_adjust_hook({
+                let adjustments = Arc::clone(&adjustments);
+                move |adjustment| adjustments.lock().unwrap().push(adjustment.limit)
+            })
+            .with_clock(clock.clone());
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        let mut limit = 10;
+        for _ in 0..2 {
+            for _ in 0..20 {
+                controller.observe(&policy.consume(1).unwrap().rate_limit);
+            }
+
+            clock.advance(Duration::minutes(1));
+            limit = controller.adjust(limit).unwrap().limit;
+        }
+
+        // Gaps of 0.4 sum up to 0.4, then 0.8.
+        assert_eq!(*adjustments.lock().unwrap(), [14, 25]);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::sync::Mutex;
+use crate::{Duration, LocalDateTime, LocalTime};
+use chrono::TimeZone;
+use std::sync::Arc;
+
+/// Source of the current time used by the policies.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> LocalDateTime;
+}
+
+/// Lets several owners share one clock.
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> LocalDateTime {
+        (**self).now()
+    }
+}
+
+/// Reads the system time. Used by every policy unless configured otherwise.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> LocalDateTime {
+        LocalTime::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// Clones share the same time, so a test can keep one handle and give
+/// another to the policy under test.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<LocalDateTime>>,
+}
+
+impl MockClock {
+    pub fn new(now: LocalDateTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock();
+        *now += duration;
+    }
+
+    pub fn set(&self, now: LocalDateTime) {
+        *self.now.lock() = now;
+    }
+}
+
+impl Default for MockClock {
+    /// Starts at the current system time, truncated to the millisecond
+    /// resolution the policies work with.
+    fn default() -> Self {
+        let now = LocalTime::now().timestamp_millis();
+
+        Self::new(LocalTime.timestamp_millis_opt(now).unwrap())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> LocalDateTime {
+        *self.now.lock()
+    }
+}
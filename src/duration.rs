@@ -0,0 +1,93 @@
+use crate::error::ParseDurationError;
+use crate::Duration;
+
+/// Parses a human readable duration such as `"15 minutes"`, `"1h30m"` or `"500ms"`.
+///
+/// The input is a sequence of `<number><unit>` pairs, optionally separated by
+/// whitespace or commas. Supported units:
+///
+/// * `ms`, `msec`, `millisecond(s)`
+/// * `s`, `sec(s)`, `second(s)`
+/// * `m`, `min(s)`, `minute(s)`
+/// * `h`, `hr(s)`, `hour(s)`
+/// * `d`, `day(s)`
+/// * `w`, `week(s)`
+pub fn parse(input: &str) -> Result<Duration, ParseDurationError> {
+    let mut chars = input.trim().chars().peekable();
+    let mut total = Duration::zero();
+
+    if chars.peek().is_none() {
+        return Err(ParseDurationError::EmptyError);
+    }
+
+    while chars.peek().is_some() {
+        let mut number = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+            number.push(c);
+        }
+
+        if number.is_empty() {
+            return Err(ParseDurationError::InvalidNumberError);
+        }
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut unit = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_alphabetic()) {
+            unit.push(c.to_ascii_lowercase());
+        }
+
+        let value: i64 = number
+            .parse()
+            .map_err(|_| ParseDurationError::InvalidNumberError)?;
+
+        let part = match unit.as_str() {
+            "ms" | "msec" | "millisecond" | "milliseconds" => Duration::try_milliseconds(value),
+            "s" | "sec" | "secs" | "second" | "seconds" => Duration::try_seconds(value),
+            "m" | "min" | "mins" | "minute" | "minutes" => Duration::try_minutes(value),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Duration::try_hours(value),
+            "d" | "day" | "days" => Duration::try_days(value),
+            "w" | "week" | "weeks" => Duration::try_weeks(value),
+            "" => return Err(ParseDurationError::MissingUnitError),
+            _ => return Err(ParseDurationError::UnknownUnitError(unit)),
+        };
+
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or(ParseDurationError::OverflowError)?;
+
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_and_compound_durations() {
+        assert_eq!(parse("500ms").unwrap(), Duration::milliseconds(500));
+        assert_eq!(parse("15 minutes").unwrap(), Duration::minutes(15));
+        assert_eq!(parse("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse("1 day, 2 hours").unwrap(), Duration::hours(26));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        assert!(matches!(parse(""), Err(ParseDurationError::EmptyError)));
+        assert!(matches!(
+            parse("15"),
+            Err(ParseDurationError::MissingUnitError)
+        ));
+        assert!(matches!(
+            parse("m"),
+            Err(ParseDurationError::InvalidNumberError)
+        ));
+        assert!(matches!(
+            parse("3 fortnights"),
+            Err(ParseDurationError::UnknownUnitError(_))
+        ));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::policy::vectors::{Decision, StoredState};
+use crate::RateLimit;
+
 #[derive(Debug, thiserror::Error)]
 pub enum BuilderError {
     #[error("")]
@@ -7,20 +10,111 @@ pub enum BuilderError {
     PolicyNotConfiguredError,
 }
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum PolicyError {
+    #[error("The limit must be greater than zero")]
     ZeroLimitError,
+
+    #[error("The key must not be empty")]
     EmptyKeyError,
+
+    #[error("The fraction must be between 0 and 1")]
+    InvalidFractionError,
+
+    #[error("The rate must refill a positive amount of tokens over a positive duration")]
+    InvalidRateError,
+
+    #[error("A compound policy needs at least one policy")]
+    NoPoliciesError,
+
+    #[error("The minimum limit must not exceed the maximum limit")]
+    InvalidLimitRangeError,
+
+    #[error("The bucket count must divide the interval in milliseconds")]
+    InvalidBucketCountError,
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgumentError(&'static str),
+
+    #[error("No quota named `{0}`")]
+    UnknownQuotaError(String),
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ReserveError {
     #[error("Cannot reserve more tokens ({requested}) than the size of the rate limiter ({max})")]
-    TooManyTokensError { requested: usize, max: usize },
+    TooManyTokensError { requested: u64, max: u64 },
 
-    #[error("")]
-    MaxWaitDurationExceededError,
+    #[error("The tokens will not be available within the maximum wait duration")]
+    MaxWaitDurationExceededError { rate_limit: RateLimit },
+
+    #[error("The event happened before the oldest window the policy still counts")]
+    TooLateError,
+
+    /// Only returned by policies in strict mode.
+    #[error(transparent)]
+    PolicyError(#[from] PolicyError),
 }
 
 #[derive(Debug)]
 pub struct RateLimitExceededError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LimiterError {
+    #[error(transparent)]
+    PolicyError(#[from] PolicyError),
+
+    #[error(transparent)]
+    ReserveError(#[from] ReserveError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseDurationError {
+    #[error("Duration string is empty")]
+    EmptyError,
+
+    #[error("Expected a number in the duration string")]
+    InvalidNumberError,
+
+    #[error("Expected a unit after the number in the duration string")]
+    MissingUnitError,
+
+    #[error("Unknown duration unit `{0}`")]
+    UnknownUnitError(String),
+
+    #[error("Duration is out of range")]
+    OverflowError,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VectorError {
+    #[error("Line {0} of the vectors is malformed")]
+    MalformedLineError(usize),
+
+    #[error("Unknown algorithm `{0}`")]
+    UnknownAlgorithmError(String),
+
+    #[error("Step {step} of vector `{vector}` expected {expected:?}, got {actual:?}")]
+    MismatchError {
+        vector: String,
+        step: usize,
+        expected: Decision,
+        actual: Decision,
+    },
+
+    #[error(
+        "Step {step} of vector `{vector}` expected the stored state {expected:?}, got {actual:?}"
+    )]
+    StateMismatchError {
+        vector: String,
+        step: usize,
+        expected: StoredState,
+        actual: Option<StoredState>,
+    },
+
+    #[error(transparent)]
+    PolicyError(#[from] PolicyError),
+
+    #[error(transparent)]
+    ReserveError(#[from] ReserveError),
+}
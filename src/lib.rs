@@ -1,16 +1,35 @@
+pub mod duration;
 pub mod error;
 pub mod policy;
+pub mod simple;
 pub mod storage;
+pub mod sync;
 
+mod acceptance_controller;
+mod clock;
+mod quota_tracker;
+mod random;
 mod rate_limit;
 mod reservation;
+mod scheduler;
+mod state_snapshot;
+mod tenant_quotas;
+mod unit;
 
 use chrono::DateTime;
 use error::BuilderError;
 use policy::Policy;
 
+pub use acceptance_controller::{AcceptanceController, AdjustHook, LimitAdjustment};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use quota_tracker::QuotaTracker;
+pub use random::{RandomSource, XorShiftRandom};
 pub use rate_limit::RateLimit;
 pub use reservation::Reservation;
+pub use scheduler::Scheduler;
+pub use state_snapshot::StateSnapshot;
+pub use tenant_quotas::{TenantQuotas, TenantState};
+pub use unit::Unit;
 
 pub(crate) use chrono::Local as LocalTime;
 pub(crate) type LocalDateTime = DateTime<LocalTime>;
@@ -23,6 +42,12 @@ pub struct RateLimiterBuilder<P: Policy> {
     policy: Option<P>,
 }
 
+impl<P: Policy> Default for RateLimiterBuilder<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<P: Policy> RateLimiterBuilder<P> {
     pub fn new() -> Self {
         Self {
@@ -46,9 +71,9 @@ impl<P: Policy> RateLimiterBuilder<P> {
             return Err(BuilderError::KeyNotConfiguredError);
         }
 
-        let Some(policy) = self.policy else {
+        if self.policy.is_none() {
             return Err(BuilderError::PolicyNotConfiguredError);
-        };
+        }
 
         Ok(())
     }
@@ -56,10 +81,6 @@ impl<P: Policy> RateLimiterBuilder<P> {
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-    use crate::policy::FixedWindowPolicy;
-    use crate::storage::InMemoryStorage;
-
     #[test]
     fn abs() {}
 }
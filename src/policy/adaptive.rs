@@ -0,0 +1,175 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::{FixedWindowPolicy, FixedWindowState, Policy};
+use crate::storage::Storage;
+use crate::{Clock, Duration, LocalDateTime, Reservation, SystemClock, Unit};
+use std::sync::Arc;
+
+/// A fixed window whose limit tunes itself to the health of a downstream service.
+///
+/// The limit follows additive-increase/multiplicative-decrease: every
+/// [`Self::report_success()`] raises it by the configured increase and every
+/// [`Self::report_failure()`] multiplies it by the decrease factor, always within
+/// `min_limit..=max_limit`. A new key starts at `max_limit`. The learned limit is
+/// persisted as the `max_size` of the key's [`FixedWindowState`].
+pub struct AdaptivePolicy<'a, Store: Storage<FixedWindowState, FixedWindowState>> {
+    min_limit: u64,
+    max_limit: u64,
+    key: String,
+    interval: Duration,
+    increase: u64,
+    decrease_factor: f64,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Arc<dyn Clock>,
+}
+
+impl<Store: Storage<FixedWindowState, FixedWindowState>> Policy for AdaptivePolicy<'_, Store> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        let limit = self.get_limit();
+
+        FixedWindowPolicy::new(limit, self.key.clone(), self.interval, &mut *self.storage)
+            .expect("the limit and the key are validated by AdaptivePolicy::new")
+            .with_unit(self.unit.clone())
+            .with_clock(Arc::clone(&self.clock))
+            .reserve(tokens, max_time)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        let limit = self.get_limit();
+
+        FixedWindowPolicy::new(limit, self.key.clone(), self.interval, &mut *self.storage)
+            .expect("the limit and the key are validated by AdaptivePolicy::new")
+            .with_clock(Arc::clone(&self.clock))
+            .refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<FixedWindowState, FixedWindowState>> AdaptivePolicy<'a, Store> {
+    /// Creates a policy allowing between `min_limit` and `max_limit` tokens per `interval`.
+    /// The limit increases by 1 per success and halves per failure unless configured otherwise.
+    pub fn new(
+        min_limit: u64,
+        max_limit: u64,
+        key: String,
+        interval: Duration,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if min_limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if min_limit > max_limit {
+            return Err(PolicyError::InvalidLimitRangeError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        Ok(Self {
+            min_limit,
+            max_limit,
+            key,
+            interval,
+            increase: 1,
+            decrease_factor: 0.5,
+            storage,
+            unit: Unit::default(),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Sets how many tokens every reported success adds to the limit.
+    pub fn with_increase(mut self, increase: u64) -> Self {
+        self.increase = increase;
+        self
+    }
+
+    /// Sets the factor, clamped between `0.0` and `1.0`, every reported failure multiplies the limit by.
+    pub fn with_decrease_factor(mut self, decrease_factor: f64) -> Self {
+        self.decrease_factor = decrease_factor.clamp(0., 1.);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the limit currently learned for the key.
+    pub fn get_limit(&self) -> u64 {
+        self.storage
+            .fetch(self.key.as_str())
+            .map_or(self.max_limit, |state| state.max_size)
+    }
+
+    /// Reports that a call allowed by this policy succeeded, raising the limit.
+    pub fn report_success(&mut self) {
+        let increase = self.increase;
+        self.adjust(|limit| limit.saturating_add(increase));
+    }
+
+    /// Reports that a call allowed by this policy failed, lowering the limit.
+    pub fn report_failure(&mut self) {
+        let decrease_factor = self.decrease_factor;
+        self.adjust(|limit| (limit as f64 * decrease_factor).floor() as u64);
+    }
+
+    fn adjust<F: FnOnce(u64) -> u64>(&mut self, adjust: F) {
+        let mut state = self.storage.fetch(self.key.as_str()).unwrap_or_else(|| {
+            FixedWindowState::new(self.key.clone(), &self.interval, self.max_limit)
+        });
+
+        state.max_size = adjust(state.max_size).clamp(self.min_limit, self.max_limit);
+        self.storage.save(&self.key, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn limit_follows_the_reported_feedback() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            AdaptivePolicy::new(2, 10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_increase(2)
+                .with_clock(clock.clone());
+
+        assert_eq!(policy.get_limit(), 10);
+        policy.report_success();
+        assert_eq!(policy.get_limit(), 10);
+
+        policy.report_failure();
+        policy.report_failure();
+        assert_eq!(policy.get_limit(), 2);
+        policy.report_failure();
+        assert_eq!(policy.get_limit(), 2);
+
+        policy.report_success();
+        let rate_limit = policy.consume(4).unwrap().rate_limit;
+        assert!(rate_limit.is_accepted());
+        assert_eq!(rate_limit.get_limit(), 4);
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+}
@@ -0,0 +1,429 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::{Policy, HIT_COUNT_OVERFLOW_FACTOR};
+use crate::storage::{State, Storage};
+use crate::{
+    ChronoTimestampMillis, Clock, Duration, LocalDateTime, LocalTime, RateLimit, Reservation,
+    SystemClock, Unit,
+};
+use chrono::TimeZone;
+
+/// A sliding window split into a fixed number of buckets.
+///
+/// The interval is divided into `buckets` buckets aligned to the epoch, and a
+/// request counts against its bucket until that bucket leaves the interval.
+/// Counting is exact to one bucket width, so more buckets mean more accuracy and
+/// a bigger state, unlike [`crate::policy::SlidingWindowPolicy`] which keeps two
+/// windows and weights the previous one.
+pub struct BucketedSlidingWindowPolicy<
+    'a,
+    Store: Storage<BucketedSlidingWindowState, BucketedSlidingWindowState>,
+> {
+    limit: u64,
+    key: String,
+    interval: Duration,
+    buckets: usize,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl<Store: Storage<BucketedSlidingWindowState, BucketedSlidingWindowState>> Policy
+    for BucketedSlidingWindowPolicy<'_, Store>
+{
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens > self.limit {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.limit,
+            });
+        }
+
+        let now = self.clock.now();
+        let mut state = self.storage.fetch(self.key.as_str()).unwrap_or_else(|| {
+            BucketedSlidingWindowState::new(self.key.clone(), &self.interval, self.buckets)
+        });
+
+        state.advance(&now);
+        let available_tokens = self.limit.saturating_sub(state.get_hit_count());
+
+        let reservation = if tokens == 0 {
+            let retry_after = if available_tokens > 0 {
+                now
+            } else {
+                LocalTime::timestamp_millis_opt(
+                    &LocalTime,
+                    now.timestamp_millis() + state.calculate_time_for_tokens(self.limit, 1, &now),
+                )
+                .unwrap()
+            };
+
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens,
+                    retry_after,
+                    accepted: true,
+                    limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else if available_tokens >= tokens {
+            state.add(tokens, self.limit, &now);
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens: self.limit.saturating_sub(state.get_hit_count()),
+                    retry_after: now,
+                    accepted: true,
+                    limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else {
+            let wait_duration = state.calculate_time_for_tokens(self.limit, tokens, &now);
+            let retry_after =
+                LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + wait_duration)
+                    .unwrap();
+
+            if let Some(max_time) = max_time {
+                if wait_duration > max_time {
+                    return Err(ReserveError::MaxWaitDurationExceededError {
+                        rate_limit: RateLimit {
+                            available_tokens,
+                            retry_after,
+                            accepted: false,
+                            limit: self.limit,
+                            reset_at: state.get_reset_time(&now),
+                            unit: self.unit.clone(),
+                        },
+                    });
+                }
+            }
+
+            state.add(tokens, self.limit, &now);
+
+            Reservation {
+                time_to_act: retry_after,
+                rate_limit: RateLimit {
+                    available_tokens: 0,
+                    retry_after,
+                    accepted: false,
+                    limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        };
+
+        if tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.advance(&self.clock.now());
+            state.refund(tokens);
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<BucketedSlidingWindowState, BucketedSlidingWindowState>>
+    BucketedSlidingWindowPolicy<'a, Store>
+{
+    /// Allows `limit` tokens per `interval`, counted in `buckets` buckets. The
+    /// interval in milliseconds must be a multiple of the bucket count.
+    pub fn new(
+        limit: u64,
+        key: String,
+        interval: Duration,
+        buckets: usize,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        // Buckets of equal whole milliseconds must cover the interval exactly.
+        let interval_ms = interval.num_milliseconds();
+        if buckets == 0
+            || interval_ms <= 0
+            || i64::try_from(buckets).map_or(true, |buckets| interval_ms % buckets != 0)
+        {
+            return Err(PolicyError::InvalidBucketCountError);
+        }
+
+        Ok(Self {
+            limit,
+            key,
+            interval,
+            buckets,
+            storage,
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BucketedSlidingWindowState {
+    pub key: String,
+    pub bucket_width: ChronoTimestampMillis,
+    /// Hits per bucket, a ring indexed by the bucket number modulo its length.
+    pub buckets: Vec<u64>,
+    /// Number of the newest bucket, counted in bucket widths since the epoch.
+    pub current_bucket: i64,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+}
+
+impl State<BucketedSlidingWindowState> for BucketedSlidingWindowState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.bucket_width.saturating_mul(self.buckets.len() as i64)).unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
+    }
+}
+
+impl BucketedSlidingWindowState {
+    pub fn new(key: String, interval: &Duration, buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+
+        Self {
+            key,
+            bucket_width: (interval.num_milliseconds() / buckets as i64).max(1),
+            buckets: vec![0; buckets],
+            current_bucket: 0,
+            created_at: None,
+            last_hit_at: None,
+        }
+    }
+
+    /// Empties the buckets that left the interval since the last update.
+    pub fn advance(&mut self, now: &LocalDateTime) {
+        let bucket = now.timestamp_millis().div_euclid(self.bucket_width);
+        let len = self.buckets.len() as i64;
+        let stale = (bucket - self.current_bucket).clamp(0, len);
+
+        for number in (bucket - stale + 1)..=bucket {
+            self.buckets[number.rem_euclid(len) as usize] = 0;
+        }
+
+        self.current_bucket = self.current_bucket.max(bucket);
+    }
+
+    /// Adds `hits` to the current bucket, capping it at [`HIT_COUNT_OVERFLOW_FACTOR`]
+    /// times `max_size`. Returns true when the cap was hit. Expects an advanced state.
+    pub fn add(&mut self, hits: u64, max_size: u64, now: &LocalDateTime) -> bool {
+        let max_hit_count = max_size.saturating_mul(HIT_COUNT_OVERFLOW_FACTOR);
+        let index = self.index(self.current_bucket);
+        let hit_count = self.buckets[index].saturating_add(hits);
+
+        self.buckets[index] = hit_count.min(max_hit_count);
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+
+        hit_count > max_hit_count
+    }
+
+    /// Removes `tokens` from the buckets, current bucket first. Expects an advanced state.
+    pub fn refund(&mut self, mut tokens: u64) {
+        let len = self.buckets.len() as i64;
+
+        for number in ((self.current_bucket - len + 1)..=self.current_bucket).rev() {
+            let index = self.index(number);
+            let refunded = tokens.min(self.buckets[index]);
+
+            self.buckets[index] -= refunded;
+            tokens -= refunded;
+
+            if tokens == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Returns the hits of every bucket within the interval. Expects an advanced state.
+    pub fn get_hit_count(&self) -> u64 {
+        self.buckets
+            .iter()
+            .fold(0, |count, hits| count.saturating_add(*hits))
+    }
+
+    /// Returns the milliseconds until `tokens` tokens fit within `max_size`. Expects an advanced state.
+    pub fn calculate_time_for_tokens(
+        &self,
+        max_size: u64,
+        tokens: u64,
+        now: &LocalDateTime,
+    ) -> i64 {
+        let mut excess = self
+            .get_hit_count()
+            .saturating_sub(max_size.saturating_sub(tokens));
+
+        if excess == 0 {
+            return 0;
+        }
+
+        let len = self.buckets.len() as i64;
+        for number in (self.current_bucket - len + 1)..=self.current_bucket {
+            excess = excess.saturating_sub(self.buckets[self.index(number)]);
+
+            if excess == 0 {
+                return ((number + len) * self.bucket_width - now.timestamp_millis()).max(0);
+            }
+        }
+
+        0
+    }
+
+    /// Returns the moment every bucket with hits has left the interval.
+    pub fn get_reset_time(&self, now: &LocalDateTime) -> LocalDateTime {
+        let len = self.buckets.len() as i64;
+        let newest = ((self.current_bucket - len + 1)..=self.current_bucket)
+            .rev()
+            .find(|number| self.buckets[self.index(*number)] > 0);
+
+        match newest {
+            Some(number) => {
+                LocalTime::timestamp_millis_opt(&LocalTime, (number + len) * self.bucket_width)
+                    .unwrap()
+            }
+            None => *now,
+        }
+    }
+
+    fn index(&self, number: i64) -> usize {
+        number.rem_euclid(self.buckets.len() as i64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::conformance;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn bucketed_sliding_window_policy_conforms() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = BucketedSlidingWindowPolicy::new(
+            5,
+            "key".into(),
+            Duration::minutes(1),
+            6,
+            &mut storage,
+        )
+        .unwrap()
+        .with_clock(clock.clone());
+
+        conformance::run(
+            &mut policy,
+            &clock,
+            5,
+            Duration::minutes(1),
+            5,
+            Duration::minutes(1),
+        );
+    }
+
+    #[test]
+    fn buckets_must_divide_the_interval() {
+        let mut storage = InMemoryStorage::new();
+
+        for (interval, buckets) in [(1_000, 3), (1_000, 0), (10, 20)] {
+            assert!(matches!(
+                BucketedSlidingWindowPolicy::new(
+                    5,
+                    "key".into(),
+                    Duration::milliseconds(interval),
+                    buckets,
+                    &mut storage,
+                ),
+                Err(PolicyError::InvalidBucketCountError)
+            ));
+        }
+
+        assert!(BucketedSlidingWindowPolicy::new(
+            5,
+            "key".into(),
+            Duration::milliseconds(1_000),
+            4,
+            &mut storage,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn hits_leave_with_their_bucket() {
+        let clock = MockClock::new(LocalTime.timestamp_millis_opt(1_000_000).unwrap());
+        let mut storage = InMemoryStorage::new();
+        let mut policy = BucketedSlidingWindowPolicy::new(
+            4,
+            "key".into(),
+            Duration::seconds(10),
+            5,
+            &mut storage,
+        )
+        .unwrap()
+        .with_clock(clock.clone());
+
+        policy.consume(3).unwrap();
+        clock.advance(Duration::seconds(5));
+        policy.consume(1).unwrap();
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(rejected.get_retry_after().timestamp_millis(), 1_010_000);
+
+        clock.advance(Duration::seconds(5));
+        assert_eq!(
+            policy.consume(3).unwrap().rate_limit.get_remaining_tokens(),
+            0
+        );
+    }
+}
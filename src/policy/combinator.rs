@@ -0,0 +1,366 @@
+use crate::error::ReserveError;
+use crate::policy::Policy;
+use crate::{LocalDateTime, Reservation};
+
+/// Accepts only when both policies accept. Built with [`Policy::and()`].
+///
+/// The first policy is asked first and the second one is skipped when it
+/// rejects the request. Tokens taken from the first policy are refunded to it
+/// when the second one rejects.
+pub struct And<A: Policy, B: Policy> {
+    pub(crate) first: A,
+    pub(crate) second: B,
+}
+
+impl<A: Policy, B: Policy> Policy for And<A, B> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        let first = self.first.reserve(tokens, max_time)?;
+        let second = match self.second.reserve(tokens, max_time) {
+            Ok(second) => second,
+            Err(error) => {
+                self.first.refund(tokens);
+                return Err(error);
+            }
+        };
+
+        Ok(stricter(first, second))
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        self.first.refund(tokens);
+        self.second.refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.first.now()
+    }
+}
+
+impl<A: Policy, B: Policy> And<A, B> {
+    /// Returns the composed policies.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+/// Accepts when either policy accepts. Built with [`Policy::or()`].
+///
+/// The second policy is only asked when the first one cannot serve the request
+/// within the maximum wait duration. If neither can, the error that retries
+/// sooner is returned. Refunds go to the policy that served the last request.
+pub struct Or<A: Policy, B: Policy> {
+    first: A,
+    second: B,
+    served_by_second: bool,
+}
+
+impl<A: Policy, B: Policy> Policy for Or<A, B> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        let first_error = match self.first.reserve(tokens, max_time) {
+            Ok(reservation) => {
+                self.served_by_second = false;
+                return Ok(reservation);
+            }
+            Err(error) => error,
+        };
+
+        match (first_error, self.second.reserve(tokens, max_time)) {
+            (_, Ok(reservation)) => {
+                self.served_by_second = true;
+                Ok(reservation)
+            }
+            (
+                ReserveError::MaxWaitDurationExceededError { rate_limit: first },
+                Err(ReserveError::MaxWaitDurationExceededError { rate_limit: second }),
+            ) => Err(ReserveError::MaxWaitDurationExceededError {
+                rate_limit: if first.retry_after <= second.retry_after {
+                    first
+                } else {
+                    second
+                },
+            }),
+            (_, Err(error)) => Err(error),
+        }
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        if self.served_by_second {
+            self.second.refund(tokens);
+        } else {
+            self.first.refund(tokens);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.first.now()
+    }
+}
+
+impl<A: Policy, B: Policy> Or<A, B> {
+    /// Returns the composed policies.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+/// Uses the second policy when the first one fails. Built with [`Policy::fallback()`].
+///
+/// Running out of tokens is a decision, not a failure: a
+/// [`ReserveError::MaxWaitDurationExceededError`] from the first policy is
+/// returned as is. Every other error hands the request to the second policy.
+/// Refunds go to the policy that served the last request.
+pub struct Fallback<A: Policy, B: Policy> {
+    primary: A,
+    fallback: B,
+    served_by_fallback: bool,
+}
+
+impl<A: Policy, B: Policy> Policy for Fallback<A, B> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        match self.primary.reserve(tokens, max_time) {
+            Err(ReserveError::MaxWaitDurationExceededError { rate_limit }) => {
+                Err(ReserveError::MaxWaitDurationExceededError { rate_limit })
+            }
+            Err(_) => {
+                let result = self.fallback.reserve(tokens, max_time);
+
+                if result.is_ok() {
+                    self.served_by_fallback = true;
+                }
+
+                result
+            }
+            result => {
+                self.served_by_fallback = false;
+                result
+            }
+        }
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        if self.served_by_fallback {
+            self.fallback.refund(tokens);
+        } else {
+            self.primary.refund(tokens);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.primary.reset();
+        self.fallback.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.primary.now()
+    }
+}
+
+impl<A: Policy, B: Policy> Fallback<A, B> {
+    /// Returns the composed policies.
+    pub fn into_inner(self) -> (A, B) {
+        (self.primary, self.fallback)
+    }
+}
+
+pub(crate) fn and<A: Policy, B: Policy>(first: A, second: B) -> And<A, B> {
+    And { first, second }
+}
+
+pub(crate) fn or<A: Policy, B: Policy>(first: A, second: B) -> Or<A, B> {
+    Or {
+        first,
+        second,
+        served_by_second: false,
+    }
+}
+
+pub(crate) fn fallback<A: Policy, B: Policy>(primary: A, fallback: B) -> Fallback<A, B> {
+    Fallback {
+        primary,
+        fallback,
+        served_by_fallback: false,
+    }
+}
+
+/// Picks the reservation that restricts the caller most: the later time to act,
+/// and the rate limit of a rejecting policy or else the one with fewer tokens left.
+pub(crate) fn stricter(first: Reservation, second: Reservation) -> Reservation {
+    let time_to_act = first.time_to_act.max(second.time_to_act);
+    let rate_limit = match (first.rate_limit.accepted, second.rate_limit.accepted) {
+        (false, true) => first.rate_limit,
+        (true, false) => second.rate_limit,
+        (false, false) if first.rate_limit.retry_after >= second.rate_limit.retry_after => {
+            first.rate_limit
+        }
+        (false, false) => second.rate_limit,
+        (true, true) if first.rate_limit.available_tokens <= second.rate_limit.available_tokens => {
+            first.rate_limit
+        }
+        (true, true) => second.rate_limit,
+    };
+
+    Reservation {
+        time_to_act,
+        rate_limit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::policy::{FixedWindowPolicy, Policy};
+    use crate::storage::InMemoryStorage;
+    use crate::{Clock, Duration, MockClock};
+
+    #[test]
+    fn and_rejects_once_either_is_exhausted() {
+        let clock = MockClock::default();
+        let (mut first, mut second) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = FixedWindowPolicy::new(2, "key".into(), Duration::minutes(1), &mut first)
+            .unwrap()
+            .with_clock(clock.clone())
+            .and(
+                FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut second)
+                    .unwrap()
+                    .with_clock(clock.clone()),
+            );
+
+        let rate_limit = policy.consume(1).unwrap().rate_limit;
+        assert!(rate_limit.is_accepted());
+        assert_eq!(rate_limit.get_limit(), 2);
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn or_accepts_while_either_has_tokens() {
+        let clock = MockClock::default();
+        let (mut first, mut second) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut first)
+            .unwrap()
+            .with_clock(clock.clone())
+            .or(
+                FixedWindowPolicy::new(1, "key".into(), Duration::minutes(2), &mut second)
+                    .unwrap()
+                    .with_clock(clock.clone()),
+            );
+
+        let start = clock.now();
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(rejected.get_retry_after(), start + Duration::minutes(1));
+    }
+
+    #[test]
+    fn fallback_takes_over_on_errors_only() {
+        let clock = MockClock::default();
+        let (mut first, mut second) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut first)
+            .unwrap()
+            .with_clock(clock.clone())
+            .fallback(
+                FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut second)
+                    .unwrap()
+                    .with_clock(clock.clone()),
+            );
+
+        assert_eq!(policy.consume(3).unwrap().rate_limit.get_limit(), 5);
+        assert_eq!(policy.consume(1).unwrap().rate_limit.get_limit(), 1);
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(rejected.get_limit(), 1);
+    }
+
+    #[test]
+    fn and_refunds_the_first_policy_when_the_second_rejects() {
+        let clock = MockClock::default();
+        let (mut first, mut second) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut first)
+            .unwrap()
+            .with_clock(clock.clone())
+            .and(
+                FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut second)
+                    .unwrap()
+                    .with_clock(clock.clone()),
+            );
+
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        let (mut first, _) = policy.into_inner();
+        let rate_limit = first.reserve(0, None).unwrap().rate_limit;
+        assert_eq!(rate_limit.get_remaining_tokens(), 4);
+    }
+
+    #[test]
+    fn or_and_fallback_refund_the_policy_that_served() {
+        let clock = MockClock::default();
+        let (mut first, mut second) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut first)
+            .unwrap()
+            .with_clock(clock.clone())
+            .or(
+                FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut second)
+                    .unwrap()
+                    .with_clock(clock.clone()),
+            );
+
+        policy.consume(1).unwrap();
+        policy.consume(2).unwrap();
+        policy.refund(2);
+
+        let (mut first, mut second) = policy.into_inner();
+        assert_eq!(
+            first
+                .reserve(0, None)
+                .unwrap()
+                .rate_limit
+                .get_remaining_tokens(),
+            0
+        );
+        assert_eq!(
+            second
+                .reserve(0, None)
+                .unwrap()
+                .rate_limit
+                .get_remaining_tokens(),
+            5
+        );
+
+        let (mut first, mut second) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut first)
+            .unwrap()
+            .with_clock(clock.clone())
+            .fallback(
+                FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut second)
+                    .unwrap()
+                    .with_clock(clock.clone()),
+            );
+
+        policy.consume(3).unwrap();
+        policy.refund(3);
+
+        let (_, mut fallback) = policy.into_inner();
+        assert_eq!(
+            fallback
+                .reserve(0, None)
+                .unwrap()
+                .rate_limit
+                .get_remaining_tokens(),
+            5
+        );
+    }
+}
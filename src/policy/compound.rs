@@ -0,0 +1,173 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::combinator::stricter;
+use crate::policy::{BoxedPolicy, Policy};
+use crate::{LocalDateTime, Reservation};
+
+/// Accepts only when every inner policy accepts, e.g. "100 per minute and 2000 per hour".
+///
+/// The returned reservation acts once every inner reservation can act, and
+/// carries the most restrictive rate limit. The policies are asked in order of
+/// their remaining tokens, fewest first, and the first rejection stops the
+/// request before the remaining policies count it. Policies asked before a
+/// rejection get their tokens refunded.
+pub struct CompoundPolicy<'a> {
+    policies: Vec<BoxedPolicy<'a>>,
+}
+
+impl Policy for CompoundPolicy<'_> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens == 0 {
+            return self.peek();
+        }
+
+        let mut order = Vec::with_capacity(self.policies.len());
+        for (index, policy) in self.policies.iter_mut().enumerate() {
+            let peek = policy.reserve(0, None)?;
+            order.push((peek.rate_limit.available_tokens, index));
+        }
+
+        order.sort_unstable();
+
+        let mut result: Option<Reservation> = None;
+        let mut reserved: Vec<usize> = Vec::with_capacity(order.len());
+        for (_, index) in order {
+            let reservation = match self.policies[index].reserve(tokens, max_time) {
+                Ok(reservation) => reservation,
+                Err(error) => {
+                    for index in reserved {
+                        self.policies[index].refund(tokens);
+                    }
+
+                    return Err(error);
+                }
+            };
+            reserved.push(index);
+
+            result = Some(match result {
+                Some(previous) => stricter(previous, reservation),
+                None => reservation,
+            });
+        }
+
+        Ok(result.unwrap())
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        for policy in &mut self.policies {
+            policy.refund(tokens);
+        }
+    }
+
+    fn reset(&mut self) {
+        for policy in &mut self.policies {
+            policy.reset();
+        }
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.policies[0].now()
+    }
+}
+
+impl<'a> CompoundPolicy<'a> {
+    pub fn new(policies: Vec<BoxedPolicy<'a>>) -> Result<Self, PolicyError> {
+        if policies.is_empty() {
+            return Err(PolicyError::NoPoliciesError);
+        }
+
+        Ok(Self { policies })
+    }
+
+    /// Adds another policy that must accept every request.
+    pub fn with_policy<P: Policy + 'a>(mut self, policy: P) -> Self {
+        self.policies.push(Box::new(policy));
+        self
+    }
+
+    pub fn into_inner(self) -> Vec<BoxedPolicy<'a>> {
+        self.policies
+    }
+
+    fn peek(&mut self) -> Result<Reservation, ReserveError> {
+        let mut result: Option<Reservation> = None;
+        for policy in &mut self.policies {
+            let reservation = policy.reserve(0, None)?;
+
+            result = Some(match result {
+                Some(previous) => stricter(previous, reservation),
+                None => reservation,
+            });
+        }
+
+        Ok(result.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FixedWindowPolicy;
+    use crate::storage::{InMemoryStorage, Storage};
+    use crate::{Duration, MockClock};
+
+    #[test]
+    fn enforces_every_policy_and_reports_the_strictest() {
+        let clock = MockClock::default();
+        let (mut minutely, mut hourly) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = CompoundPolicy::new(vec![Box::new(
+            FixedWindowPolicy::new(3, "key".into(), Duration::minutes(1), &mut minutely)
+                .unwrap()
+                .with_clock(clock.clone()),
+        )])
+        .unwrap()
+        .with_policy(
+            FixedWindowPolicy::new(4, "key".into(), Duration::hours(1), &mut hourly)
+                .unwrap()
+                .with_clock(clock.clone()),
+        );
+
+        assert_eq!(policy.consume(3).unwrap().rate_limit.get_limit(), 3);
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        clock.advance(Duration::minutes(1));
+        let rate_limit = policy.consume(1).unwrap().rate_limit;
+        assert!(rate_limit.is_accepted());
+        assert_eq!(rate_limit.get_limit(), 4);
+        assert_eq!(rate_limit.get_remaining_tokens(), 0);
+
+        // The hourly limit rejects first, the minutely window is left untouched.
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(rejected.get_limit(), 4);
+
+        drop(policy);
+        assert_eq!(minutely.fetch("key").unwrap().hit_count, 1);
+    }
+
+    #[test]
+    fn refunds_policies_that_reserved_before_a_rejection() {
+        let clock = MockClock::default();
+        let (mut secondly, mut hourly) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policy = CompoundPolicy::new(vec![Box::new(
+            FixedWindowPolicy::new(1, "key".into(), Duration::seconds(1), &mut secondly)
+                .unwrap()
+                .with_clock(clock.clone()),
+        )])
+        .unwrap()
+        .with_policy(
+            FixedWindowPolicy::new(2, "key".into(), Duration::hours(1), &mut hourly)
+                .unwrap()
+                .with_clock(clock.clone()),
+        );
+
+        policy.consume(1).unwrap();
+        clock.advance(Duration::seconds(1));
+        policy.consume(1).unwrap();
+
+        // The per second policy can serve within the wait, the hourly one cannot.
+        assert!(policy.reserve(1, Some(1_000)).is_err());
+
+        drop(policy);
+        assert_eq!(secondly.fetch("key").unwrap().hit_count, 1);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::Policy;
+use crate::storage::{State, Storage};
+use crate::sync::Mutex;
+use crate::{
+    ChronoTimestampMillis, Clock, Duration, LocalDateTime, LocalTime, RateLimit, Reservation,
+    SystemClock, Unit,
+};
+use chrono::TimeZone;
+
+/// Caps the number of operations in flight instead of the operations per interval.
+///
+/// [`Self::acquire()`] takes slots and returns a [`ConcurrencyGuard`] that gives
+/// them back when dropped. Slots taken through [`Policy::reserve()`] or
+/// [`Policy::consume()`] are not released automatically, pass them to
+/// [`Self::release()`] once the operation is done.
+///
+/// Slots cannot be reserved ahead of time: when none is free, the request is
+/// rejected with a [`ReserveError::MaxWaitDurationExceededError`] whatever the
+/// maximum wait duration. Since no release time is known, the retry time is a
+/// hint, one second from now by default, set with [`Self::with_retry_hint()`].
+/// The storage is shared behind a mutex so the guards can release their slots
+/// while the policy keeps handing out new ones.
+pub struct ConcurrencyPolicy<'a, Store: Storage<ConcurrencyState, ConcurrencyState>> {
+    limit: u64,
+    key: String,
+    storage: &'a Mutex<Store>,
+    unit: Unit,
+    retry_hint: Duration,
+    clock: Box<dyn Clock>,
+}
+
+impl<Store: Storage<ConcurrencyState, ConcurrencyState>> Policy for ConcurrencyPolicy<'_, Store> {
+    fn reserve(
+        &mut self,
+        tokens: u64,
+        _max_time: Option<i64>,
+    ) -> Result<Reservation, ReserveError> {
+        self.take(tokens)
+    }
+
+    /// Same as [`ConcurrencyPolicy::release()`].
+    fn refund(&mut self, tokens: u64) {
+        release(self.storage, &self.key, tokens);
+    }
+
+    /// Frees every slot, including the ones held by live guards.
+    fn reset(&mut self) {
+        self.storage.lock().delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<ConcurrencyState, ConcurrencyState>> ConcurrencyPolicy<'a, Store> {
+    /// Allows at most `limit` slots of `key` to be taken at the same time.
+    pub fn new(limit: u64, key: String, storage: &'a Mutex<Store>) -> Result<Self, PolicyError> {
+        if limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        Ok(Self {
+            limit,
+            key,
+            storage,
+            unit: Unit::default(),
+            retry_hint: Duration::seconds(1),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Sets how long after now rejected requests are told to retry, so clients
+    /// following the retry time do not spin against a full policy.
+    pub fn with_retry_hint(mut self, retry_hint: Duration) -> Self {
+        self.retry_hint = retry_hint;
+        self
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Takes `tokens` slots, released when the returned guard is dropped.
+    pub fn acquire(&self, tokens: u64) -> Result<ConcurrencyGuard<'a, Store>, ReserveError> {
+        let reservation = self.take(tokens)?;
+
+        Ok(ConcurrencyGuard {
+            key: self.key.clone(),
+            tokens,
+            storage: self.storage,
+            reservation,
+        })
+    }
+
+    /// Gives back `tokens` slots taken through [`Policy::reserve()`] or [`Policy::consume()`].
+    pub fn release(&mut self, tokens: u64) {
+        release(self.storage, &self.key, tokens);
+    }
+
+    fn take(&self, tokens: u64) -> Result<Reservation, ReserveError> {
+        if tokens > self.limit {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.limit,
+            });
+        }
+
+        let now = self.clock.now();
+        let mut storage = self.storage.lock();
+        let mut state = storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| ConcurrencyState::new(self.key.clone()));
+
+        let available_tokens = self.limit.saturating_sub(state.in_flight);
+
+        if available_tokens < tokens {
+            let retry_after = now + self.retry_hint.max(Duration::zero());
+
+            return Err(ReserveError::MaxWaitDurationExceededError {
+                rate_limit: RateLimit {
+                    available_tokens,
+                    retry_after,
+                    accepted: false,
+                    limit: self.limit,
+                    reset_at: retry_after,
+                    unit: self.unit.clone(),
+                },
+            });
+        }
+
+        if tokens > 0 {
+            state.take(tokens, &now);
+            storage.save(&self.key, state.clone());
+        }
+
+        Ok(Reservation {
+            time_to_act: now,
+            rate_limit: RateLimit {
+                available_tokens: self.limit.saturating_sub(state.in_flight),
+                retry_after: now,
+                accepted: true,
+                limit: self.limit,
+                reset_at: now,
+                unit: self.unit.clone(),
+            },
+        })
+    }
+}
+
+fn release<Store: Storage<ConcurrencyState, ConcurrencyState>>(
+    storage: &Mutex<Store>,
+    key: &str,
+    tokens: u64,
+) {
+    if tokens == 0 {
+        return;
+    }
+
+    let mut storage = storage.lock();
+
+    if let Some(mut state) = storage.fetch(key) {
+        state.in_flight = state.in_flight.saturating_sub(tokens);
+        storage.save(key, state);
+    }
+}
+
+/// Slots taken by [`ConcurrencyPolicy::acquire()`], released on drop.
+pub struct ConcurrencyGuard<'a, Store: Storage<ConcurrencyState, ConcurrencyState>> {
+    key: String,
+    tokens: u64,
+    storage: &'a Mutex<Store>,
+    reservation: Reservation,
+}
+
+impl<Store: Storage<ConcurrencyState, ConcurrencyState>> ConcurrencyGuard<'_, Store> {
+    pub fn get_reservation(&self) -> &Reservation {
+        &self.reservation
+    }
+}
+
+impl<Store: Storage<ConcurrencyState, ConcurrencyState>> Drop for ConcurrencyGuard<'_, Store> {
+    fn drop(&mut self) {
+        release(self.storage, &self.key, self.tokens);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConcurrencyState {
+    pub key: String,
+    pub in_flight: u64,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+}
+
+impl State<ConcurrencyState> for ConcurrencyState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        // Slots in flight must outlive any TTL, they are only freed by releases.
+        u64::MAX
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
+    }
+}
+
+impl ConcurrencyState {
+    pub fn new(key: String) -> Self {
+        Self {
+            key,
+            in_flight: 0,
+            created_at: None,
+            last_hit_at: None,
+        }
+    }
+
+    pub fn take(&mut self, tokens: u64, now: &LocalDateTime) {
+        self.in_flight = self.in_flight.saturating_add(tokens);
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn guards_release_their_slots() {
+        let storage = Mutex::new(InMemoryStorage::new());
+        let mut policy = ConcurrencyPolicy::new(2, "key".into(), &storage).unwrap();
+
+        let first = policy.acquire(1).unwrap();
+        let second = policy.acquire(1).unwrap();
+        assert_eq!(
+            second
+                .get_reservation()
+                .get_rate_limit()
+                .get_remaining_tokens(),
+            0
+        );
+        assert!(policy.acquire(1).is_err());
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        drop(first);
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        assert!(policy.acquire(1).is_err());
+
+        policy.release(1);
+        drop(second);
+        assert_eq!(storage.lock().fetch("key").unwrap().in_flight, 0);
+    }
+
+    #[test]
+    fn rejections_retry_after_the_hint() {
+        let clock = MockClock::default();
+        let storage = Mutex::new(InMemoryStorage::new());
+        let mut policy = ConcurrencyPolicy::new(1, "key".into(), &storage)
+            .unwrap()
+            .with_clock(clock.clone());
+
+        let _guard = policy.acquire(1).unwrap();
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert_eq!(
+            rejected.get_retry_after(),
+            clock.now() + Duration::seconds(1)
+        );
+
+        let mut policy = policy.with_retry_hint(Duration::milliseconds(250));
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(
+            rejected.get_retry_after(),
+            clock.now() + Duration::milliseconds(250)
+        );
+    }
+}
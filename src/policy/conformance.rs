@@ -0,0 +1,195 @@
+//! Conformance checks for [`Policy`] implementations.
+//!
+//! Custom policies can run [`run()`] from their own tests (enable the `test-util`
+//! feature) to verify they keep the invariants the bundled policies guarantee.
+//! The policy must read the time from the given [`MockClock`], and must limit a
+//! single key that has not been used yet. Every violation panics with a message
+//! describing the broken expectation.
+
+use crate::policy::Policy;
+use crate::{Clock, Duration, LocalDateTime, MockClock};
+
+/// Upper bound on retries while waiting for a rejected consume to go through.
+const MAX_RETRIES: usize = 10_000;
+
+/// Runs every conformance check against `policy`, which allows `limit` tokens per `interval`.
+///
+/// `max_burst` is the most tokens the policy may accept within any rolling interval,
+/// e.g. `limit` for a sliding log but `2 * limit` for a fixed window straddled by the
+/// interval. `max_wait` is the longest a rejected consume may have to wait, e.g. one
+/// interval for a fixed window but two for a sliding window whose full current window
+/// has to decay.
+pub fn run<P: Policy>(
+    policy: &mut P,
+    clock: &MockClock,
+    limit: u64,
+    interval: Duration,
+    max_burst: u64,
+    max_wait: Duration,
+) {
+    peek_records_nothing(policy, limit);
+    accepts_up_to_limit(policy, limit);
+    rejects_over_limit(policy, clock, max_wait);
+    retry_after_is_honored(policy, clock, max_wait);
+    resets_after_idle(policy, clock, limit, interval);
+    never_exceeds_max_burst(policy, clock, interval, max_burst);
+}
+
+fn peek_records_nothing<P: Policy>(policy: &mut P, limit: u64) {
+    for _ in 0..2 {
+        assert_eq!(
+            policy.peek().unwrap().get_remaining_tokens(),
+            limit,
+            "peek() must not take tokens"
+        );
+    }
+}
+
+fn accepts_up_to_limit<P: Policy>(policy: &mut P, limit: u64) {
+    for consumed in 1..=limit {
+        let rate_limit = policy.consume(1).unwrap().rate_limit;
+
+        assert!(
+            rate_limit.is_accepted(),
+            "consume #{consumed} must be accepted within a limit of {limit}"
+        );
+        assert_eq!(
+            rate_limit.get_remaining_tokens(),
+            limit - consumed,
+            "remaining tokens must decrease with every accepted consume"
+        );
+    }
+}
+
+fn rejects_over_limit<P: Policy>(policy: &mut P, clock: &MockClock, max_wait: Duration) {
+    let now = clock.now();
+    let rate_limit = policy.consume(1).unwrap().rate_limit;
+
+    assert!(
+        !rate_limit.is_accepted(),
+        "a consume over the limit must be rejected"
+    );
+    assert!(
+        rate_limit.get_retry_after() > now,
+        "a rejected consume must retry after a moment in the future"
+    );
+    assert!(
+        rate_limit.get_retry_after() <= now + max_wait,
+        "a rejected consume must not retry later than {max_wait} from now"
+    );
+}
+
+fn retry_after_is_honored<P: Policy>(policy: &mut P, clock: &MockClock, max_wait: Duration) {
+    let deadline = clock.now() + max_wait;
+
+    for _ in 0..MAX_RETRIES {
+        let now = clock.now();
+        let rate_limit = policy.consume(1).unwrap().rate_limit;
+
+        if rate_limit.is_accepted() {
+            return;
+        }
+
+        assert!(
+            rate_limit.get_retry_after() > now,
+            "a rejected consume must retry after a moment in the future"
+        );
+        assert!(
+            now <= deadline,
+            "retrying at retry_after must succeed within {max_wait}"
+        );
+
+        clock.set(rate_limit.get_retry_after());
+    }
+
+    panic!("retrying at retry_after never succeeded");
+}
+
+fn resets_after_idle<P: Policy>(policy: &mut P, clock: &MockClock, limit: u64, interval: Duration) {
+    clock.advance(interval * 2 + Duration::milliseconds(1));
+
+    let rate_limit = policy.peek().unwrap();
+    assert_eq!(
+        rate_limit.get_remaining_tokens(),
+        limit,
+        "the full limit must be available after two idle intervals"
+    );
+}
+
+/// Sends a steady stream of requests and checks that no rolling interval accepts
+/// more than `max_burst` tokens.
+fn never_exceeds_max_burst<P: Policy>(
+    policy: &mut P,
+    clock: &MockClock,
+    interval: Duration,
+    max_burst: u64,
+) {
+    let steps = max_burst.saturating_mul(4).clamp(1, 1_000);
+    let step = (interval / steps as i32).max(Duration::milliseconds(1));
+    let mut accepted: Vec<LocalDateTime> = Vec::new();
+
+    for _ in 0..steps * 5 {
+        if policy.consume(1).unwrap().rate_limit.is_accepted() {
+            accepted.push(clock.now());
+        }
+
+        clock.advance(step);
+    }
+
+    for (index, start) in accepted.iter().enumerate() {
+        let within_interval = accepted[index..]
+            .iter()
+            .take_while(|time| **time < *start + interval)
+            .count() as u64;
+
+        assert!(
+            within_interval <= max_burst,
+            "accepted {within_interval} tokens within one interval, at most {max_burst} are allowed"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FixedWindowPolicy, SlidingWindowPolicy};
+    use crate::storage::InMemoryStorage;
+
+    #[test]
+    fn fixed_window_policy_conforms() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        run(
+            &mut policy,
+            &clock,
+            5,
+            Duration::minutes(1),
+            10,
+            Duration::minutes(1),
+        );
+    }
+
+    #[test]
+    fn sliding_window_policy_conforms() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            SlidingWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        run(
+            &mut policy,
+            &clock,
+            5,
+            Duration::minutes(1),
+            5,
+            Duration::minutes(2),
+        );
+    }
+}
@@ -0,0 +1,205 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::{FixedWindowState, Policy};
+use crate::storage::Storage;
+use crate::{Clock, Duration, LocalDateTime, LocalTime, RateLimit, Reservation, SystemClock, Unit};
+use chrono::TimeZone;
+
+/// A [`crate::policy::FixedWindowPolicy`] whose limit and interval are fixed at
+/// compile time, e.g. `ConstFixedWindowPolicy::<_, 100, 60_000>` for 100 tokens per minute.
+///
+/// The policy holds no configuration besides the key, so the window math works on
+/// constants the compiler can fold. A zero limit or interval fails to compile.
+/// It shares [`FixedWindowState`] with the runtime policy, without its spacing,
+/// alignment and adaptive interval options.
+pub struct ConstFixedWindowPolicy<
+    'a,
+    Store: Storage<FixedWindowState, FixedWindowState>,
+    const LIMIT: u64,
+    const INTERVAL_MS: i64,
+> {
+    key: String,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl<
+        Store: Storage<FixedWindowState, FixedWindowState>,
+        const LIMIT: u64,
+        const INTERVAL_MS: i64,
+    > Policy for ConstFixedWindowPolicy<'_, Store, LIMIT, INTERVAL_MS>
+{
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens > LIMIT {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: LIMIT,
+            });
+        }
+
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| FixedWindowState::new(self.key.clone(), &Self::interval(), LIMIT));
+        state.max_size = LIMIT;
+        state.interval = INTERVAL_MS;
+
+        let now = self.clock.now();
+        let available_tokens = state.get_available_tokens(&now);
+
+        let reservation = if tokens == 0 {
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens: available_tokens.unwrap_or(0),
+                    retry_after: now,
+                    accepted: true,
+                    limit: LIMIT,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else if available_tokens.is_some_and(|available_tokens| available_tokens >= tokens) {
+            state.add(Some(tokens), Some(&now));
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens: state.get_available_tokens(&now).unwrap_or(0),
+                    retry_after: now,
+                    accepted: true,
+                    limit: LIMIT,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else {
+            let wait_duration = state.calculate_time_for_tokens(tokens, &now);
+            let retry_after =
+                LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + wait_duration)
+                    .unwrap();
+
+            if let Some(max_time) = max_time {
+                if wait_duration > max_time {
+                    return Err(ReserveError::MaxWaitDurationExceededError {
+                        rate_limit: RateLimit {
+                            available_tokens: available_tokens.unwrap_or(0),
+                            retry_after,
+                            accepted: false,
+                            limit: LIMIT,
+                            reset_at: state.get_reset_time(&now),
+                            unit: self.unit.clone(),
+                        },
+                    });
+                }
+            }
+
+            state.add(Some(tokens), Some(&now));
+
+            Reservation {
+                time_to_act: retry_after,
+                rate_limit: RateLimit {
+                    available_tokens: 0,
+                    retry_after,
+                    accepted: false,
+                    limit: LIMIT,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        };
+
+        if tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.interval = INTERVAL_MS;
+            state.refund(tokens, &self.clock.now());
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<
+        'a,
+        Store: Storage<FixedWindowState, FixedWindowState>,
+        const LIMIT: u64,
+        const INTERVAL_MS: i64,
+    > ConstFixedWindowPolicy<'a, Store, LIMIT, INTERVAL_MS>
+{
+    pub fn new(key: String, storage: &'a mut Store) -> Result<Self, PolicyError> {
+        const {
+            assert!(LIMIT > 0, "the limit must not be zero");
+            assert!(INTERVAL_MS > 0, "the interval must be positive");
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        Ok(Self {
+            key,
+            storage,
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    fn interval() -> Duration {
+        Duration::milliseconds(INTERVAL_MS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn enforces_the_const_limit() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = ConstFixedWindowPolicy::<_, 2, 60_000>::new("key".into(), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+
+        assert!(policy.consume(2).unwrap().rate_limit.is_accepted());
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(
+            rejected.get_retry_after(),
+            clock.now() + Duration::minutes(1)
+        );
+
+        clock.advance(Duration::minutes(1));
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        assert!(policy.consume(3).is_err());
+    }
+}
@@ -1,37 +1,57 @@
 use crate::error::{PolicyError, ReserveError};
-use crate::policy::Policy;
+use crate::policy::{rescale_hits, CapHook, Policy, HIT_COUNT_OVERFLOW_FACTOR};
 use crate::storage::{State, Storage};
-use crate::{Duration, LocalDateTime, LocalTime, RateLimit, Reservation};
+use crate::{
+    ChronoTimestampMillis, Clock, Duration, LocalDateTime, LocalTime, RateLimit, Reservation,
+    StateSnapshot, SystemClock, Unit,
+};
 use chrono::TimeZone;
 
+/// Where the windows of a [`FixedWindowPolicy`] start.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WindowAlignment {
+    /// A window starts with the first hit after the previous one ended.
+    #[default]
+    FirstHit,
+    /// Windows start at multiples of the interval in local time, e.g. a one
+    /// minute window always resets at :00 seconds and a one day window at midnight.
+    Calendar,
+}
+
 pub struct FixedWindowPolicy<'a, Store: Storage<FixedWindowState, FixedWindowState>> {
-    limit: usize,
+    limit: u64,
     key: String,
     interval: chrono::Duration,
     storage: &'a mut Store,
+    unit: Unit,
+    max_adaptive_interval: Option<Duration>,
+    min_spacing: Option<Duration>,
+    alignment: WindowAlignment,
+    max_carry_over: Option<u64>,
+    overdraft: u64,
+    strict: bool,
+    on_cap: Option<CapHook>,
+    clock: Box<dyn Clock>,
 }
 
 impl<Store: Storage<FixedWindowState, FixedWindowState>> Policy for FixedWindowPolicy<'_, Store> {
-    fn reserve(
-        &mut self,
-        tokens: usize,
-        max_time: Option<i64>,
-    ) -> Result<Reservation, ReserveError> {
-        if tokens > self.limit {
-            // Cannot reserve more tokens than the size of the rate limiter.
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        self.check_arguments(None, max_time)?;
+
+        let now = self.clock.now();
+        let mut state = self.load_state(&now);
+
+        if tokens > state.get_max_tokens() {
+            // Cannot reserve more tokens than the window can ever take.
             return Err(ReserveError::TooManyTokensError {
                 requested: tokens,
-                max: self.limit,
+                max: state.get_max_tokens(),
             });
         }
-
-        let mut state = self
-            .storage
-            .fetch(self.key.as_str())
-            .unwrap_or_else(|| FixedWindowState::new(self.key.clone(), &self.interval, self.limit));
-
-        let now = LocalTime::now();
         let available_tokens = state.get_available_tokens(&now);
+        let spacing_wait = self
+            .min_spacing
+            .map_or(0, |spacing| state.get_spacing_wait(&spacing, &now));
 
         let reservation: Reservation = if tokens == 0 {
             let wait_duration = state.calculate_time_for_tokens(tokens, &now);
@@ -40,47 +60,75 @@ impl<Store: Storage<FixedWindowState, FixedWindowState>> Policy for FixedWindowP
                     .unwrap();
 
             Reservation {
-                time_to_act: retry_after.clone(),
+                time_to_act: retry_after,
                 rate_limit: RateLimit {
                     available_tokens: available_tokens.unwrap_or(0),
                     retry_after,
                     accepted: true,
                     limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
                 },
             }
-        } else if available_tokens.is_some() && available_tokens.unwrap() >= tokens {
-            state.add(Some(tokens), Some(&now));
+        } else if spacing_wait == 0
+            && available_tokens.is_some()
+            && available_tokens.unwrap() >= tokens
+        {
+            self.report_cap(state.add(Some(tokens), Some(&now)));
+            state.last_accepted_at = Some(now.timestamp_millis());
             Reservation {
-                time_to_act: now.clone(),
+                time_to_act: now,
                 rate_limit: RateLimit {
                     available_tokens: state.get_available_tokens(&now).unwrap_or(0),
-                    retry_after: now.clone(),
+                    retry_after: now,
                     accepted: true,
                     limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
                 },
             }
         } else {
-            let wait_duration = state.calculate_time_for_tokens(tokens, &now);
+            let wait_duration = state
+                .calculate_time_for_tokens(tokens, &now)
+                .max(spacing_wait);
+            let retry_after =
+                LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + wait_duration)
+                    .unwrap();
 
             if let Some(max_time) = max_time {
                 if wait_duration > max_time {
-                    return Err(ReserveError::MaxWaitDurationExceededError);
+                    let rate_limit = RateLimit {
+                        available_tokens: available_tokens.unwrap_or(0),
+                        retry_after,
+                        accepted: false,
+                        limit: self.limit,
+                        reset_at: state.get_reset_time(&now),
+                        unit: self.unit.clone(),
+                    };
+
+                    // The tokens are not recorded, but the rejection still widens the window.
+                    if self.max_adaptive_interval.is_some() {
+                        state.violations = state.violations.saturating_add(1);
+                        self.storage.save(&self.key, state);
+                    }
+
+                    return Err(ReserveError::MaxWaitDurationExceededError { rate_limit });
                 }
             }
 
-            state.add(Some(tokens), Some(&now));
-
-            let retry_after =
-                LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + wait_duration)
-                    .unwrap();
+            self.report_cap(state.add(Some(tokens), Some(&now)));
+            state.violations = state.violations.saturating_add(1);
+            state.last_accepted_at = Some(retry_after.timestamp_millis());
 
             Reservation {
-                time_to_act: retry_after.clone(),
+                time_to_act: retry_after,
                 rate_limit: RateLimit {
                     available_tokens: state.get_available_tokens(&now).unwrap_or(0),
                     retry_after,
                     accepted: false,
                     limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
                 },
             }
         };
@@ -92,14 +140,41 @@ impl<Store: Storage<FixedWindowState, FixedWindowState>> Policy for FixedWindowP
         Ok(reservation)
     }
 
-    fn consume(&mut self, tokens: usize) -> Result<Reservation, ReserveError> {
-        self.reserve(tokens, None)
+    fn refund(&mut self, tokens: u64) {
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.refund(tokens, &self.clock.now());
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn penalize(&mut self, tokens: u64) -> Result<(), ReserveError> {
+        self.check_arguments(Some(tokens), None)?;
+
+        if tokens == 0 {
+            return Ok(());
+        }
+
+        let now = self.clock.now();
+        let mut state = self.load_state(&now);
+
+        self.report_cap(state.add(Some(tokens), Some(&now)));
+        self.storage.save(&self.key, state);
+
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
     }
 }
 
 impl<'a, Store: Storage<FixedWindowState, FixedWindowState>> FixedWindowPolicy<'a, Store> {
     pub fn new(
-        limit: usize,
+        limit: u64,
         key: String,
         interval: Duration,
         storage: &'a mut Store,
@@ -117,17 +192,266 @@ impl<'a, Store: Storage<FixedWindowState, FixedWindowState>> FixedWindowPolicy<'
             key,
             interval,
             storage,
+            unit: Unit::default(),
+            max_adaptive_interval: None,
+            min_spacing: None,
+            alignment: WindowAlignment::default(),
+            max_carry_over: None,
+            overdraft: 0,
+            strict: false,
+            on_cap: None,
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Same as [`Self::new()`], in strict mode: a non-positive interval fails here,
+    /// and penalties of zero tokens as well as negative waits, spacings and adaptive
+    /// intervals fail with a [`PolicyError::InvalidArgumentError`] instead of being
+    /// silently accepted.
+    pub fn new_strict(
+        limit: u64,
+        key: String,
+        interval: Duration,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if interval <= Duration::zero() {
+            return Err(PolicyError::InvalidArgumentError(
+                "the interval must be positive",
+            ));
+        }
+
+        let mut policy = Self::new(limit, key, interval, storage)?;
+        policy.strict = true;
+
+        Ok(policy)
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Enables the adaptive mode: every window that ended with rejected requests doubles
+    /// the window interval of the key (up to `max_interval`), and every window without
+    /// rejections halves it back towards the configured interval.
+    pub fn with_adaptive_interval(mut self, max_interval: Duration) -> Self {
+        self.max_adaptive_interval = Some(max_interval);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Calls `on_cap` with the key whenever its hit count is capped at
+    /// [`HIT_COUNT_OVERFLOW_FACTOR`] times the limit.
+    pub fn with_cap_hook<F: FnMut(&str) + Send + 'static>(mut self, on_cap: F) -> Self {
+        self.on_cap = Some(Box::new(on_cap));
+        self
+    }
+
+    /// Sets where the windows start, at the first hit by default.
+    pub fn with_alignment(mut self, alignment: WindowAlignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Keeps accepted requests at least `min_spacing` apart, even when tokens remain.
+    pub fn with_min_spacing(mut self, min_spacing: Duration) -> Self {
+        self.min_spacing = Some(min_spacing);
+        self
+    }
+
+    /// Rolls the tokens a window left unused, up to `max_carry_over`, into the next
+    /// window, e.g. "unused calls roll over to the next month".
+    ///
+    /// Only a window directly following the previous one receives its unused tokens,
+    /// and carried tokens can roll over again. The remaining tokens reported by the
+    /// rate limits include the carried ones, so they can exceed the limit.
+    pub fn with_carry_over(mut self, max_carry_over: u64) -> Self {
+        self.max_carry_over = Some(max_carry_over);
+        self
+    }
+
+    /// Lets a window take up to `overdraft` tokens beyond the limit, repaid from
+    /// the budget of the next window, to absorb legitimate bursts.
+    ///
+    /// The next window counts the overdrawn tokens as its first hits and cannot be
+    /// overdrawn itself, so it accepts the limit minus the debt. Every window repays
+    /// up to the limit, idle or not, so a debt larger than the limit takes several
+    /// windows to repay. The remaining tokens reported by the rate limits include
+    /// the overdraft when it is available.
+    pub fn with_overdraft(mut self, overdraft: u64) -> Self {
+        self.overdraft = overdraft;
+        self
+    }
+
+    /// Changes the limit of the key, keeping the current window.
+    ///
+    /// The hits of the current window are rescaled so the same share of the limit
+    /// stays used, e.g. 5 hits out of 10 become 10 hits out of 20.
+    pub fn set_limit(&mut self, limit: u64) -> Result<(), PolicyError> {
+        if limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.hit_count = rescale_hits(state.hit_count, self.limit, limit);
+            state.max_size = limit;
+            self.storage.save(&self.key, state);
+        }
+
+        self.limit = limit;
+
+        Ok(())
+    }
+
+    /// Changes the interval of the key. The current window keeps its start and
+    /// its hits, and ends `interval` after its start.
+    pub fn set_interval(&mut self, interval: Duration) {
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.interval = interval.num_milliseconds();
+            self.storage.save(&self.key, state);
+        }
+
+        self.interval = interval;
+    }
+
+    /// Returns how long until `tokens` tokens are available, without recording anything.
+    pub fn time_until_available(&self, tokens: u64) -> Result<Duration, ReserveError> {
+        let now = self.clock.now();
+        let state = self.load_state(&now);
+
+        if tokens > state.get_max_tokens() {
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: state.get_max_tokens(),
+            });
+        }
+        let mut wait = state.calculate_time_for_tokens(tokens, &now).max(0);
+
+        if let (Some(spacing), true) = (self.min_spacing, tokens > 0) {
+            wait = wait.max(state.get_spacing_wait(&spacing, &now));
+        }
+
+        Ok(Duration::milliseconds(wait))
+    }
+
+    /// Validates the arguments of a call in strict mode, `hits` being the hits it adds as is.
+    fn check_arguments(&self, hits: Option<u64>, max_time: Option<i64>) -> Result<(), PolicyError> {
+        if !self.strict {
+            return Ok(());
+        }
+
+        if hits == Some(0) {
+            return Err(PolicyError::InvalidArgumentError(
+                "at least one hit must be added",
+            ));
+        }
+
+        if max_time.is_some_and(|max_time| max_time < 0) {
+            return Err(PolicyError::InvalidArgumentError(
+                "the maximum wait must not be negative",
+            ));
+        }
+
+        if self
+            .min_spacing
+            .is_some_and(|spacing| spacing < Duration::zero())
+        {
+            return Err(PolicyError::InvalidArgumentError(
+                "the minimum spacing must not be negative",
+            ));
+        }
+
+        if self
+            .max_adaptive_interval
+            .is_some_and(|interval| interval < Duration::zero())
+        {
+            return Err(PolicyError::InvalidArgumentError(
+                "the adaptive interval must not be negative",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Tells the cap hook, if any, that the hit count of the key was capped.
+    fn report_cap(&mut self, capped: bool) {
+        if let (true, Some(on_cap)) = (capped, &mut self.on_cap) {
+            on_cap(&self.key);
+        }
+    }
+
+    /// Fetches the state of the key and brings its window up to date with `now`.
+    fn load_state(&self, now: &LocalDateTime) -> FixedWindowState {
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| FixedWindowState::new(self.key.clone(), &self.interval, self.limit));
+        // The configured limit wins over the one the state was created with.
+        state.max_size = self.limit;
+
+        if let Some(max_interval) = &self.max_adaptive_interval {
+            state.adapt_interval(now, &self.interval, max_interval);
+        }
+
+        state.overdraft = self.overdraft;
+
+        if self.max_carry_over.is_some() || self.overdraft > 0 {
+            let max_carry_over = self.max_carry_over.unwrap_or(0);
+            state.roll_over(now, max_carry_over, self.alignment);
+        }
+
+        if self.alignment == WindowAlignment::Calendar {
+            state.align_window(now);
+        }
+
+        state
+    }
+
+    /// Returns what the current window of the key counts, or None when the key
+    /// has no state. The window is brought up to date first, so a window following
+    /// an overdrawn one counts the debt and its limit includes the carried over
+    /// tokens. Otherwise a window that has ended counts no hits and ends now.
+    pub fn state(&self) -> Option<StateSnapshot> {
+        self.storage.fetch(self.key.as_str())?;
+        let now = self.clock.now();
+        let state = self.load_state(&now);
+        let ended = (now.timestamp_millis() - state.timer) >= state.interval;
+
+        Some(StateSnapshot {
+            hit_count: if ended { 0 } else { state.hit_count },
+            window_end: state.get_reset_time(&now),
+            limit: state.get_window_size(),
+            interval: Duration::milliseconds(state.interval),
         })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FixedWindowState {
     pub key: String,
-    pub hit_count: usize,
+    pub hit_count: u64,
     pub interval: i64, // chrono timestamp millis
-    pub max_size: usize,
+    pub max_size: u64,
     pub timer: i64,
+    /// Rejected reservations within the current window, used by the adaptive mode.
+    pub violations: u64,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+    /// When the last accepted or reserved request acts, used by the minimum spacing.
+    pub last_accepted_at: Option<ChronoTimestampMillis>,
+    /// Unused tokens of the previous window available on top of the limit, used by the carry-over.
+    pub carried_over: u64,
+    /// Tokens a window may take beyond its size, repaid by the next window.
+    pub overdraft: u64,
+    /// Tokens overdrawn by the previous window and counted as hits of this one.
+    /// The overdraft is not available while they are repaid.
+    pub owed: u64,
 }
 
 impl State<FixedWindowState> for FixedWindowState {
@@ -135,57 +459,774 @@ impl State<FixedWindowState> for FixedWindowState {
         self.key.clone()
     }
 
-    fn get_expiration_time(&self) -> usize {
-        self.interval as usize
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.interval).unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
     }
 }
 
 impl FixedWindowState {
-    pub fn new(key: String, interval: &chrono::Duration, max_size: usize) -> Self {
+    pub fn new(key: String, interval: &chrono::Duration, max_size: u64) -> Self {
         Self {
             key,
             hit_count: 0,
             interval: interval.num_milliseconds(),
             max_size,
             timer: 0,
+            violations: 0,
+            created_at: None,
+            last_hit_at: None,
+            last_accepted_at: None,
+            carried_over: 0,
+            overdraft: 0,
+            owed: 0,
         }
     }
 
-    pub fn add(&mut self, hits: Option<usize>, now: Option<&LocalDateTime>) {
-        let hits = hits.unwrap_or(1); // TODO : maybe error if hits == 0 ?
+    /// Widens or narrows the window interval once the current window has ended,
+    /// depending on whether it saw any rejected reservations.
+    pub fn adapt_interval(&mut self, now: &LocalDateTime, base: &Duration, max: &Duration) {
+        if self.timer == 0 || (now.timestamp_millis() - self.timer) < self.interval {
+            return;
+        }
+
+        self.interval = if self.violations > 0 {
+            self.interval.saturating_mul(2).min(max.num_milliseconds())
+        } else {
+            (self.interval / 2).max(base.num_milliseconds())
+        };
+        self.violations = 0;
+    }
+
+    /// Starts a new window at the last multiple of the interval in local time
+    /// once the current window has ended.
+    pub fn align_window(&mut self, now: &LocalDateTime) {
+        let now_ms = now.timestamp_millis();
+
+        if self.interval <= 0 || (now_ms - self.timer) < self.interval {
+            return;
+        }
+
+        self.timer = self.aligned_timer(now);
+        self.hit_count = 0;
+        self.carried_over = 0;
+        self.owed = 0;
+    }
+
+    /// Starts a new window once the current one has ended. When the new window
+    /// directly follows it, the unused tokens are carried over, up to
+    /// `max_carry_over`. The overdrawn tokens, less the limit for every window
+    /// that passed in between, are counted as its first hits.
+    pub fn roll_over(
+        &mut self,
+        now: &LocalDateTime,
+        max_carry_over: u64,
+        alignment: WindowAlignment,
+    ) {
+        let now_ms = now.timestamp_millis();
+
+        if self.timer == 0 || self.interval <= 0 || (now_ms - self.timer) < self.interval {
+            return;
+        }
+
+        let window_size = self.get_window_size();
+        let skipped_windows = u64::try_from((now_ms - self.timer) / self.interval - 1).unwrap_or(0);
+        let carried_over = if skipped_windows == 0 {
+            window_size
+                .saturating_sub(self.hit_count)
+                .min(max_carry_over)
+        } else {
+            0
+        };
+        let owed = self
+            .hit_count
+            .saturating_sub(window_size)
+            .min(self.overdraft)
+            .saturating_sub(self.max_size.saturating_mul(skipped_windows));
+
+        self.carried_over = carried_over;
+        self.hit_count = owed;
+        self.owed = owed;
+        self.timer = match alignment {
+            WindowAlignment::FirstHit => now_ms,
+            WindowAlignment::Calendar => self.aligned_timer(now),
+        };
+    }
+
+    /// Returns the limit of the current window, including the carried over tokens.
+    pub fn get_window_size(&self) -> u64 {
+        self.max_size.saturating_add(self.carried_over)
+    }
+
+    /// Returns the tokens the current window may take, including the overdraft
+    /// unless the window repays the previous one.
+    pub fn get_budget(&self) -> u64 {
+        if self.owed > 0 {
+            return self.get_window_size();
+        }
+
+        self.get_window_size().saturating_add(self.overdraft)
+    }
+
+    /// Returns the most tokens a single request may take: the window size and the
+    /// overdraft, which a request can wait for when the window repays a debt.
+    pub fn get_max_tokens(&self) -> u64 {
+        self.get_window_size().saturating_add(self.overdraft)
+    }
+
+    fn aligned_timer(&self, now: &LocalDateTime) -> ChronoTimestampMillis {
+        let offset = i64::from(now.offset().local_minus_utc()) * 1_000;
+
+        (now.timestamp_millis() + offset).div_euclid(self.interval) * self.interval - offset
+    }
+
+    /// Adds `hits` to the current window, capping the hit count at
+    /// [`HIT_COUNT_OVERFLOW_FACTOR`] times the budget of the window with its overdraft,
+    /// so the cap stays above the budget and any debt whatever the carry-over and
+    /// overdraft. Returns true when the cap was hit.
+    pub fn add(&mut self, hits: Option<u64>, now: Option<&LocalDateTime>) -> bool {
+        // Strict policies reject zero hits before they get here.
+        let hits = hits.unwrap_or(1);
         let now = now
-            .map(|date| date.clone())
-            .unwrap_or_else(|| LocalTime::now())
+            .copied()
+            .unwrap_or_else(LocalTime::now)
             .timestamp_millis();
 
-        if (now - self.timer) > self.interval {
+        if (now - self.timer) >= self.interval {
             // reset window
             self.timer = now;
             self.hit_count = 0;
+            self.carried_over = 0;
+            self.owed = 0;
         }
 
-        self.hit_count += hits;
+        let max_hit_count = self
+            .get_max_tokens()
+            .saturating_mul(HIT_COUNT_OVERFLOW_FACTOR);
+        let hit_count = self.hit_count.saturating_add(hits);
+        self.hit_count = hit_count.min(max_hit_count);
+        self.created_at.get_or_insert(now);
+        self.last_hit_at = Some(now);
+
+        hit_count > max_hit_count
+    }
+
+    /// Gives `tokens` back to the current window. Once the window has ended there
+    /// is nothing left to give back.
+    pub fn refund(&mut self, tokens: u64, now: &LocalDateTime) {
+        if (now.timestamp_millis() - self.timer) < self.interval {
+            self.hit_count = self.hit_count.saturating_sub(tokens);
+        }
+    }
+
+    /// Returns the milliseconds until a request is `spacing` away from the last accepted one.
+    pub fn get_spacing_wait(&self, spacing: &Duration, now: &LocalDateTime) -> i64 {
+        self.last_accepted_at.map_or(0, |last_accepted_at| {
+            (last_accepted_at + spacing.num_milliseconds() - now.timestamp_millis()).max(0)
+        })
     }
 
-    pub fn get_available_tokens(&self, now: &LocalDateTime) -> Option<usize> {
+    pub fn get_available_tokens(&self, now: &LocalDateTime) -> Option<u64> {
         let now = now.timestamp_millis();
 
-        if (now - self.timer) > self.interval {
-            return Some(self.max_size);
+        if (now - self.timer) >= self.interval {
+            return Some(self.max_size.saturating_add(self.overdraft));
         }
 
-        if self.hit_count > self.max_size {
+        if self.hit_count > self.get_budget() {
             return None; // Avoid to subtract with overflow
         }
 
-        Some(self.max_size - self.hit_count)
+        Some(self.get_budget() - self.hit_count)
     }
 
-    pub fn calculate_time_for_tokens(&self, tokens: usize, now: &LocalDateTime) -> i64 {
-        if (self.max_size - self.hit_count) >= tokens {
+    /// Returns the moment the current window ends and the full limit is available again.
+    pub fn get_reset_time(&self, now: &LocalDateTime) -> LocalDateTime {
+        if (now.timestamp_millis() - self.timer) >= self.interval {
+            return *now;
+        }
+
+        LocalTime::timestamp_millis_opt(&LocalTime, self.timer + self.interval).unwrap()
+    }
+
+    pub fn calculate_time_for_tokens(&self, tokens: u64, now: &LocalDateTime) -> i64 {
+        if self.get_budget().saturating_sub(self.hit_count) >= tokens {
             return 0;
         }
 
         self.timer + self.interval - now.timestamp_millis()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn over_limit_state_does_not_underflow() {
+        let now = LocalTime::now();
+        let mut state = FixedWindowState::new("key".into(), &Duration::seconds(10), 5);
+        state.add(Some(8), Some(&now));
+
+        assert_eq!(state.get_available_tokens(&now), None);
+        assert!(state.calculate_time_for_tokens(1, &now) > 0);
+    }
+
+    #[test]
+    fn hit_count_is_capped() {
+        let now = LocalTime::now();
+        let mut state = FixedWindowState::new("key".into(), &Duration::seconds(10), 5);
+
+        assert!(!state.add(Some(10), Some(&now)));
+        assert!(state.add(Some(u64::MAX), Some(&now)));
+        assert_eq!(state.hit_count, 5 * HIT_COUNT_OVERFLOW_FACTOR);
+        assert_eq!(state.get_available_tokens(&now), None);
+    }
+
+    #[test]
+    fn adaptive_interval_widens_after_violations_and_narrows_back() {
+        let base = Duration::seconds(1);
+        let max = Duration::seconds(4);
+        let mut state = FixedWindowState::new("key".into(), &base, 5);
+
+        state.timer = LocalTime::now().timestamp_millis() - 2_000;
+        state.violations = 3;
+        state.adapt_interval(&LocalTime::now(), &base, &max);
+        assert_eq!(state.interval, 2_000);
+        assert_eq!(state.violations, 0);
+
+        state.timer = LocalTime::now().timestamp_millis() - 3_000;
+        state.adapt_interval(&LocalTime::now(), &base, &max);
+        assert_eq!(state.interval, 1_000);
+    }
+
+    #[test]
+    fn penalties_count_against_the_limit() {
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage).unwrap();
+
+        policy.penalize(3).unwrap();
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 2);
+
+        // Penalties may exceed the limit, pushing the next accepted request back.
+        policy.penalize(10).unwrap();
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn refunds_only_within_the_window() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(3, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        assert!(policy.consume(3).unwrap().rate_limit.is_accepted());
+        policy.refund(1);
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        // Refunds cannot raise the available tokens above the limit.
+        policy.refund(10);
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 3);
+
+        policy.consume(3).unwrap();
+        clock.advance(Duration::minutes(1));
+        policy.refund(3);
+        assert_eq!(
+            policy.consume(3).unwrap().rate_limit.get_remaining_tokens(),
+            0
+        );
+    }
+
+    #[test]
+    fn reset_clears_the_key() {
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(2, "key".into(), Duration::minutes(1), &mut storage).unwrap();
+
+        policy.consume(2).unwrap();
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        policy.reset();
+        assert!(policy.consume(2).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn state_reports_the_current_window() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        assert!(policy.state().is_none());
+
+        let start = clock.now();
+        policy.consume(4).unwrap();
+        let state = policy.state().unwrap();
+        assert_eq!(state.get_hit_count(), 4);
+        assert_eq!(state.get_window_end(), start + Duration::minutes(1));
+        assert_eq!(state.get_limit(), 10);
+        assert_eq!(state.get_interval(), Duration::minutes(1));
+
+        clock.advance(Duration::minutes(1));
+        assert_eq!(policy.state().unwrap().get_hit_count(), 0);
+    }
+
+    #[test]
+    fn state_counts_the_debt_and_the_carried_tokens() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_overdraft(3)
+                .with_clock(clock.clone());
+
+        policy.consume(10).unwrap();
+        policy.consume(3).unwrap();
+        clock.advance(Duration::minutes(1));
+        let state = policy.state().unwrap();
+        assert_eq!(state.get_hit_count(), 3);
+        assert_eq!(state.get_limit(), 10);
+        assert_eq!(
+            policy.consume(1).unwrap().rate_limit.get_remaining_tokens(),
+            6
+        );
+
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_carry_over(5)
+                .with_clock(clock.clone());
+
+        policy.consume(8).unwrap();
+        clock.advance(Duration::minutes(1));
+        let state = policy.state().unwrap();
+        assert_eq!(state.get_hit_count(), 0);
+        assert_eq!(state.get_limit(), 12);
+        assert_eq!(state.get_window_end(), clock.now() + Duration::minutes(1));
+    }
+
+    #[test]
+    fn time_until_available_records_nothing() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(8).unwrap();
+        clock.advance(Duration::seconds(20));
+
+        assert_eq!(policy.time_until_available(2).unwrap(), Duration::zero());
+        assert_eq!(
+            policy.time_until_available(3).unwrap(),
+            Duration::seconds(40)
+        );
+        assert!(policy.time_until_available(11).is_err());
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 2);
+    }
+
+    #[test]
+    fn strict_mode_rejects_invalid_arguments() {
+        let mut storage = InMemoryStorage::new();
+        assert!(matches!(
+            FixedWindowPolicy::new_strict(10, "key".into(), Duration::zero(), &mut storage),
+            Err(PolicyError::InvalidArgumentError(_))
+        ));
+
+        let mut policy =
+            FixedWindowPolicy::new_strict(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap();
+        assert!(matches!(
+            policy.penalize(0),
+            Err(ReserveError::PolicyError(
+                PolicyError::InvalidArgumentError(_)
+            ))
+        ));
+        assert!(policy.reserve(1, Some(-1)).is_err());
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn set_limit_rescales_the_current_window() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(5).unwrap();
+        policy.set_limit(20).unwrap();
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 10);
+
+        policy.set_interval(Duration::minutes(2));
+        clock.advance(Duration::minutes(1));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 10);
+
+        assert!(policy.set_limit(0).is_err());
+    }
+
+    #[test]
+    fn unused_tokens_roll_over_up_to_the_cap() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_carry_over(5)
+                .with_clock(clock.clone());
+
+        policy.consume(8).unwrap();
+        clock.advance(Duration::minutes(1));
+        assert_eq!(
+            policy.consume(1).unwrap().rate_limit.get_remaining_tokens(),
+            11
+        );
+
+        policy.consume(1).unwrap();
+        clock.advance(Duration::minutes(1));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 15);
+        assert!(policy.consume(15).unwrap().rate_limit.is_accepted());
+
+        // An idle window in between drops the carried tokens.
+        clock.advance(Duration::minutes(2));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 10);
+    }
+
+    #[test]
+    fn sustained_load_exhausts_a_large_carried_over_window() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_carry_over(100)
+                .with_clock(clock.clone());
+
+        // Nearly idle windows build the carry-over up to its cap.
+        for _ in 0..15 {
+            policy.consume(1).unwrap();
+            clock.advance(Duration::minutes(1));
+        }
+
+        let accepted = (0..200)
+            .filter(|_| policy.consume(1).unwrap().rate_limit.is_accepted())
+            .count();
+        assert_eq!(accepted, 110);
+    }
+
+    #[test]
+    fn overdraft_is_repaid_by_the_next_window() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_overdraft(3)
+                .with_clock(clock.clone());
+
+        assert!(policy.consume(14).is_err());
+        let rate_limit = policy.consume(12).unwrap().rate_limit;
+        assert!(rate_limit.is_accepted());
+        assert_eq!(rate_limit.get_remaining_tokens(), 1);
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        // The 3 overdrawn tokens are repaid by the next window, which cannot overdraw.
+        clock.advance(Duration::minutes(1));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 7);
+        assert!(policy.consume(7).unwrap().rate_limit.is_accepted());
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        // Once repaid, the overdraft is available again.
+        clock.advance(Duration::minutes(1));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 13);
+    }
+
+    #[test]
+    fn windows_accept_the_limit_on_average_with_an_overdraft() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_overdraft(25)
+                .with_clock(clock.clone());
+
+        let mut accepted = Vec::new();
+        for _ in 0..5 {
+            let count = (0..500)
+                .filter(|_| policy.consume(1).unwrap().rate_limit.is_accepted())
+                .count();
+            accepted.push(count);
+            clock.advance(Duration::minutes(1));
+        }
+
+        // The debt of 25 takes three windows to repay, then the overdraft is back.
+        assert_eq!(accepted, [35, 0, 0, 5, 35]);
+    }
+
+    #[test]
+    fn reports_time_until_window_reset() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(1).unwrap();
+        clock.advance(Duration::seconds(20));
+        let rate_limit = policy.consume(1).unwrap().rate_limit;
+
+        assert_eq!(
+            rate_limit.time_until_reset(&clock.now()),
+            Duration::seconds(40)
+        );
+
+        clock.advance(Duration::minutes(2));
+        assert_eq!(rate_limit.time_until_reset(&clock.now()), Duration::zero());
+    }
+
+    #[test]
+    fn tracks_first_and_last_hit() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(1).unwrap();
+        clock.advance(Duration::minutes(5));
+        policy.consume(1).unwrap();
+
+        let state = storage.fetch("key").unwrap();
+        assert_eq!(state.get_created_at(), Some(start));
+        assert_eq!(state.get_last_hit_at(), Some(clock.now()));
+
+        let cutoff = clock.now() - Duration::minutes(1);
+        storage.retain(|_, state| state.get_last_hit_at().is_some_and(|at| at > cutoff));
+        assert_eq!(storage.len(), 1);
+
+        clock.advance(Duration::minutes(2));
+        let cutoff = clock.now() - Duration::minutes(1);
+        storage.retain(|_, state| state.get_last_hit_at().is_some_and(|at| at > cutoff));
+        assert!(storage.is_empty());
+    }
+
+    #[test]
+    fn keeps_accepted_requests_apart() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_min_spacing(Duration::milliseconds(200))
+                .with_clock(clock.clone());
+
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(
+            rejected.get_retry_after(),
+            start + Duration::milliseconds(200)
+        );
+
+        let reservation = policy.reserve(1, None).unwrap();
+        assert_eq!(reservation.time_to_act, start + Duration::milliseconds(200));
+
+        clock.advance(Duration::milliseconds(300));
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+        clock.advance(Duration::milliseconds(100));
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn calendar_windows_reset_on_interval_boundaries() {
+        let clock = MockClock::new(LocalTime.with_ymd_and_hms(2024, 1, 1, 12, 0, 30).unwrap());
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_alignment(WindowAlignment::Calendar)
+                .with_clock(clock.clone());
+
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert_eq!(
+            rejected.get_retry_after(),
+            LocalTime.with_ymd_and_hms(2024, 1, 1, 12, 1, 0).unwrap()
+        );
+
+        clock.advance(Duration::seconds(30));
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn rejected_consume_is_not_counted() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(2, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock);
+
+        assert!(policy.consume(2).unwrap().rate_limit.is_accepted());
+
+        let reservation = policy.consume(1).unwrap();
+        assert!(!reservation.rate_limit.is_accepted());
+        assert_eq!(reservation.time_to_act, start + Duration::minutes(1));
+        assert_eq!(
+            reservation.rate_limit.get_retry_after(),
+            start + Duration::minutes(1)
+        );
+
+        drop(policy);
+        assert_eq!(storage.fetch("key").unwrap().hit_count, 2);
+    }
+
+    #[test]
+    fn consume_at_retry_after_is_accepted() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(2, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(2).unwrap();
+        let retry_after = policy.consume(1).unwrap().rate_limit.get_retry_after();
+
+        clock.set(retry_after);
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn rate_limits_carry_the_configured_unit() {
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_unit(Unit::Bytes);
+
+        let accepted = policy.consume(5).unwrap().rate_limit;
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        let peeked = policy.reserve(0, None).unwrap().rate_limit;
+
+        assert!(accepted.is_accepted());
+        assert!(!rejected.is_accepted());
+        assert_eq!(accepted.get_unit(), &Unit::Bytes);
+        assert_eq!(rejected.get_unit(), &Unit::Bytes);
+        assert_eq!(peeked.get_unit(), &Unit::Bytes);
+    }
+
+    #[test]
+    fn rejected_consumes_widen_the_adaptive_interval() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(1, "key".into(), Duration::seconds(1), &mut storage)
+                .unwrap()
+                .with_adaptive_interval(Duration::seconds(4))
+                .with_clock(clock.clone());
+
+        policy.consume(1).unwrap();
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        // The window saw a rejection, so it doubles instead of ending after a second.
+        clock.advance(Duration::seconds(1));
+        let rate_limit = policy.reserve(0, None).unwrap().rate_limit;
+        assert_eq!(rate_limit.get_reset_at(), start + Duration::seconds(2));
+    }
+
+    #[test]
+    fn cap_hook_fires_when_the_hit_count_is_capped() {
+        let capped = Arc::new(AtomicUsize::new(0));
+        let counter = capped.clone();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(2, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_cap_hook(move |key| {
+                    assert_eq!(key, "key");
+                    counter.fetch_add(1, Ordering::Relaxed);
+                });
+
+        policy.consume(2).unwrap();
+        policy.penalize(6).unwrap();
+        assert_eq!(capped.load(Ordering::Relaxed), 0);
+
+        policy.reserve(1, None).unwrap();
+        assert_eq!(capped.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn penalty_after_the_window_ended_keeps_the_carried_tokens() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_carry_over(5)
+                .with_clock(clock.clone());
+
+        policy.consume(4).unwrap();
+        clock.advance(Duration::minutes(1));
+        policy.penalize(2).unwrap();
+
+        let rate_limit = policy.reserve(0, None).unwrap().rate_limit;
+        assert_eq!(rate_limit.get_remaining_tokens(), 13);
+    }
+
+    #[test]
+    fn sustained_load_exhausts_a_large_overdraft() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_overdraft(100)
+                .with_clock(clock.clone());
+
+        let accepted = (0..500)
+            .filter(|_| policy.consume(1).unwrap().rate_limit.is_accepted())
+            .count();
+        assert_eq!(accepted, 110);
+
+        // A penalty in a window repaying the debt must not shrink the debt.
+        clock.advance(Duration::minutes(1));
+        policy.penalize(1).unwrap();
+        clock.advance(Duration::minutes(9));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 0);
+        clock.advance(Duration::minutes(1));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 9);
+    }
+}
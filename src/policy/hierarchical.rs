@@ -0,0 +1,118 @@
+use crate::error::ReserveError;
+use crate::policy::combinator::{self, And};
+use crate::policy::Policy;
+use crate::{LocalDateTime, Reservation};
+
+/// Limits a key and a budget shared by many keys, e.g. "each user 10 per second,
+/// the whole tenant 100 per second", in one call.
+///
+/// Works as [`And`] of the key policy and the shared policy: the key policy is
+/// asked first, and when the shared policy rejects the request the tokens are
+/// refunded to the key policy, so requests rejected by the shared budget do not
+/// eat into the limit of the key. Unlike [`And`], a reset only resets the key.
+///
+/// Build one instance per request, passing the key policy of the caller and the
+/// shared policy over the common storage. The two reservations and the refund are
+/// not atomic across processes sharing the storage.
+pub struct HierarchicalPolicy<K: Policy, G: Policy>(And<K, G>);
+
+impl<K: Policy, G: Policy> Policy for HierarchicalPolicy<K, G> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        self.0.reserve(tokens, max_time)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        self.0.refund(tokens);
+    }
+
+    /// Resets the key policy only, the shared budget is left as is.
+    fn reset(&mut self) {
+        self.0.first.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.0.now()
+    }
+}
+
+impl<K: Policy, G: Policy> HierarchicalPolicy<K, G> {
+    /// Limits requests by the `key` policy and the shared `global` policy.
+    pub fn new(key: K, global: G) -> Self {
+        Self(combinator::and(key, global))
+    }
+
+    pub fn into_inner(self) -> (K, G) {
+        self.0.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FixedWindowPolicy;
+    use crate::storage::{InMemoryStorage, Storage};
+    use crate::Duration;
+
+    #[test]
+    fn shared_budget_rejections_do_not_count_against_the_key() {
+        let mut global = InMemoryStorage::new();
+        let mut users = InMemoryStorage::new();
+
+        for (user, tokens) in [("a", 2), ("b", 1)] {
+            let mut policy = HierarchicalPolicy::new(
+                FixedWindowPolicy::new(2, user.into(), Duration::minutes(1), &mut users).unwrap(),
+                FixedWindowPolicy::new(3, "tenant".into(), Duration::minutes(1), &mut global)
+                    .unwrap(),
+            );
+
+            assert!(policy.consume(tokens).unwrap().rate_limit.is_accepted());
+        }
+
+        let mut policy = HierarchicalPolicy::new(
+            FixedWindowPolicy::new(2, "b".into(), Duration::minutes(1), &mut users).unwrap(),
+            FixedWindowPolicy::new(3, "tenant".into(), Duration::minutes(1), &mut global).unwrap(),
+        );
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(rejected.get_limit(), 3);
+
+        drop(policy);
+        assert_eq!(users.fetch("b").unwrap().hit_count, 1);
+    }
+
+    #[test]
+    fn exhausted_shared_budget_leaves_the_key_count_unchanged() {
+        let mut global = InMemoryStorage::new();
+        let mut users = InMemoryStorage::new();
+        let mut policy = HierarchicalPolicy::new(
+            FixedWindowPolicy::new(5, "a".into(), Duration::minutes(1), &mut users).unwrap(),
+            FixedWindowPolicy::new(2, "tenant".into(), Duration::minutes(1), &mut global).unwrap(),
+        );
+
+        policy.consume(2).unwrap();
+        assert!(policy.reserve(1, Some(0)).is_err());
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        drop(policy);
+        assert_eq!(users.fetch("a").unwrap().hit_count, 2);
+        assert_eq!(global.fetch("tenant").unwrap().hit_count, 2);
+    }
+
+    #[test]
+    fn reset_leaves_the_shared_budget() {
+        let mut global = InMemoryStorage::new();
+        let mut users = InMemoryStorage::new();
+        let mut policy = HierarchicalPolicy::new(
+            FixedWindowPolicy::new(5, "a".into(), Duration::minutes(1), &mut users).unwrap(),
+            FixedWindowPolicy::new(5, "tenant".into(), Duration::minutes(1), &mut global).unwrap(),
+        );
+
+        policy.consume(2).unwrap();
+        policy.reset();
+
+        drop(policy);
+        assert!(users.fetch("a").is_none());
+        assert_eq!(global.fetch("tenant").unwrap().hit_count, 2);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::Policy;
+use crate::storage::{State, Storage};
+use crate::{
+    ChronoTimestampMillis, Clock, Duration, LocalDateTime, LocalTime, RateLimit, Reservation,
+    SystemClock, Unit,
+};
+use chrono::TimeZone;
+
+/// Allows at most one action per `cooldown` for a key, e.g. one password reset
+/// email per minute.
+///
+/// The state only keeps the time of the last accepted action, and the retry time
+/// is exact to the millisecond. Reservations that wait are queued one cooldown
+/// after another.
+pub struct IntervalThrottlePolicy<'a, Store: Storage<IntervalThrottleState, IntervalThrottleState>>
+{
+    key: String,
+    cooldown: Duration,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl<Store: Storage<IntervalThrottleState, IntervalThrottleState>> Policy
+    for IntervalThrottlePolicy<'_, Store>
+{
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens > 1 {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: 1,
+            });
+        }
+
+        let now = self.clock.now();
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| IntervalThrottleState::new(self.key.clone(), &self.cooldown));
+
+        let available_at = state.get_available_at(&now);
+        let wait_duration = (available_at - now).num_milliseconds();
+        let available_tokens = u64::from(wait_duration == 0);
+
+        let reservation = if tokens == 0 {
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens,
+                    retry_after: available_at,
+                    accepted: true,
+                    limit: 1,
+                    reset_at: available_at,
+                    unit: self.unit.clone(),
+                },
+            }
+        } else {
+            if let Some(max_time) = max_time {
+                if wait_duration > max_time {
+                    return Err(ReserveError::MaxWaitDurationExceededError {
+                        rate_limit: RateLimit {
+                            available_tokens,
+                            retry_after: available_at,
+                            accepted: false,
+                            limit: 1,
+                            reset_at: available_at,
+                            unit: self.unit.clone(),
+                        },
+                    });
+                }
+            }
+
+            state.last_accepted_at = Some(available_at.timestamp_millis());
+            let reset_at = state.get_available_at(&now);
+
+            Reservation {
+                time_to_act: available_at,
+                rate_limit: RateLimit {
+                    available_tokens: 0,
+                    retry_after: if wait_duration == 0 {
+                        now
+                    } else {
+                        available_at
+                    },
+                    accepted: wait_duration == 0,
+                    limit: 1,
+                    reset_at,
+                    unit: self.unit.clone(),
+                },
+            }
+        };
+
+        if tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    /// Moves the last action back by one cooldown, freeing the slot it took.
+    fn refund(&mut self, tokens: u64) {
+        if tokens == 0 {
+            return;
+        }
+
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.last_accepted_at = state
+                .last_accepted_at
+                .map(|last_accepted_at| last_accepted_at - state.cooldown);
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<IntervalThrottleState, IntervalThrottleState>>
+    IntervalThrottlePolicy<'a, Store>
+{
+    pub fn new(
+        key: String,
+        cooldown: Duration,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        Ok(Self {
+            key,
+            cooldown,
+            storage,
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IntervalThrottleState {
+    pub key: String,
+    pub cooldown: ChronoTimestampMillis,
+    /// When the last accepted or reserved action acts.
+    pub last_accepted_at: Option<ChronoTimestampMillis>,
+}
+
+impl State<IntervalThrottleState> for IntervalThrottleState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.cooldown).unwrap_or(0)
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_accepted_at
+            .and_then(|last_accepted_at| LocalTime.timestamp_millis_opt(last_accepted_at).single())
+    }
+}
+
+impl IntervalThrottleState {
+    pub fn new(key: String, cooldown: &Duration) -> Self {
+        Self {
+            key,
+            cooldown: cooldown.num_milliseconds(),
+            last_accepted_at: None,
+        }
+    }
+
+    /// Returns when the next action is allowed, now at the earliest.
+    pub fn get_available_at(&self, now: &LocalDateTime) -> LocalDateTime {
+        let now_ms = now.timestamp_millis();
+        let available_at = self.last_accepted_at.map_or(now_ms, |last_accepted_at| {
+            (last_accepted_at + self.cooldown).max(now_ms)
+        });
+
+        LocalTime::timestamp_millis_opt(&LocalTime, available_at).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::conformance;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn interval_throttle_policy_conforms() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            IntervalThrottlePolicy::new("key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        conformance::run(
+            &mut policy,
+            &clock,
+            1,
+            Duration::minutes(1),
+            1,
+            Duration::minutes(1),
+        );
+    }
+
+    #[test]
+    fn retries_exactly_one_cooldown_later() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            IntervalThrottlePolicy::new("key".into(), Duration::milliseconds(1500), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        let start = clock.now();
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        clock.advance(Duration::milliseconds(700));
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(
+            rejected.get_retry_after(),
+            start + Duration::milliseconds(1500)
+        );
+
+        clock.set(rejected.get_retry_after());
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+}
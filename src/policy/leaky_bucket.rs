@@ -0,0 +1,361 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::{Policy, Rate};
+use crate::storage::{State, Storage};
+use crate::{
+    ChronoTimestampMillis, Clock, LocalDateTime, LocalTime, RateLimit, Reservation, SystemClock,
+    Unit,
+};
+use chrono::TimeZone;
+
+/// A bucket of `capacity` tokens that leaks at a constant rate.
+///
+/// Every accepted request adds its tokens to the bucket and is scheduled to act
+/// once the tokens queued before it have leaked, so [`Reservation::get_time_to_act()`]
+/// may lie in the future even for accepted requests: waiting for it paces the
+/// calls evenly. Requests that do not fit in the bucket are rejected.
+pub struct LeakyBucketPolicy<'a, Store: Storage<LeakyBucketState, LeakyBucketState>> {
+    capacity: u64,
+    key: String,
+    rate: Rate,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl<Store: Storage<LeakyBucketState, LeakyBucketState>> Policy for LeakyBucketPolicy<'_, Store> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens > self.capacity {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.capacity,
+            });
+        }
+
+        let now = self.clock.now();
+        let mut state = self.storage.fetch(self.key.as_str()).unwrap_or_else(|| {
+            LeakyBucketState::new(self.key.clone(), self.capacity, self.rate, &now)
+        });
+
+        state.leak(&now);
+        let available_tokens = state.get_available_tokens();
+
+        let reservation = if tokens == 0 {
+            let retry_after = if available_tokens > 0 {
+                now
+            } else {
+                to_date_time(now.timestamp_millis() + state.calculate_time_for_tokens(1, &now))
+            };
+
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens,
+                    retry_after,
+                    accepted: true,
+                    limit: self.capacity,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else if available_tokens >= tokens {
+            let time_to_act = to_date_time(now.timestamp_millis() + state.time_until_empty(&now));
+            state.add(tokens, &now);
+
+            Reservation {
+                time_to_act,
+                rate_limit: RateLimit {
+                    available_tokens: state.get_available_tokens(),
+                    retry_after: now,
+                    accepted: true,
+                    limit: self.capacity,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else {
+            let wait_duration = state.calculate_time_for_tokens(tokens, &now);
+            let retry_after = to_date_time(now.timestamp_millis() + wait_duration);
+
+            if let Some(max_time) = max_time {
+                if wait_duration > max_time {
+                    return Err(ReserveError::MaxWaitDurationExceededError {
+                        rate_limit: RateLimit {
+                            available_tokens,
+                            retry_after,
+                            accepted: false,
+                            limit: self.capacity,
+                            reset_at: state.get_reset_time(&now),
+                            unit: self.unit.clone(),
+                        },
+                    });
+                }
+            }
+
+            // Queue the tokens anyway, they act once everything before them has leaked.
+            let time_to_act = to_date_time(now.timestamp_millis() + state.time_until_empty(&now));
+            state.add(tokens, &now);
+
+            Reservation {
+                time_to_act,
+                rate_limit: RateLimit {
+                    available_tokens: 0,
+                    retry_after,
+                    accepted: false,
+                    limit: self.capacity,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        };
+
+        if tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        let now = self.clock.now();
+
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.leak(&now);
+            state.refund(tokens);
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<LeakyBucketState, LeakyBucketState>> LeakyBucketPolicy<'a, Store> {
+    /// Creates a bucket holding at most `capacity` tokens, leaking at `rate`.
+    pub fn new(
+        capacity: u64,
+        key: String,
+        rate: Rate,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if capacity == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        if !rate.is_valid() {
+            return Err(PolicyError::InvalidRateError);
+        }
+
+        Ok(Self {
+            capacity,
+            key,
+            rate,
+            storage,
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+}
+
+fn to_date_time(timestamp_millis: ChronoTimestampMillis) -> LocalDateTime {
+    LocalTime::timestamp_millis_opt(&LocalTime, timestamp_millis).unwrap()
+}
+
+#[derive(Debug, Clone)]
+pub struct LeakyBucketState {
+    pub key: String,
+    /// Tokens currently in the bucket, may exceed the capacity by queued reservations.
+    pub level: u64,
+    pub capacity: u64,
+    pub rate: Rate,
+    /// When the last leak happened; partial leak progress is counted from here.
+    pub timer: ChronoTimestampMillis,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+}
+
+impl State<LeakyBucketState> for LeakyBucketState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.rate.calculate_time_for_tokens(self.capacity)).unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
+    }
+}
+
+impl LeakyBucketState {
+    pub fn new(key: String, capacity: u64, rate: Rate, now: &LocalDateTime) -> Self {
+        Self {
+            key,
+            level: 0,
+            capacity,
+            rate,
+            timer: now.timestamp_millis(),
+            created_at: None,
+            last_hit_at: None,
+        }
+    }
+
+    /// Removes the tokens leaked since the last leak.
+    pub fn leak(&mut self, now: &LocalDateTime) {
+        let now = now.timestamp_millis();
+
+        if self.level == 0 {
+            // An empty bucket does not bank leak progress.
+            self.timer = now;
+            return;
+        }
+
+        let leaked = self
+            .rate
+            .calculate_new_tokens_during_interval(now - self.timer);
+
+        if leaked == 0 {
+            return;
+        }
+
+        if leaked >= self.level {
+            self.level = 0;
+            self.timer = now;
+        } else {
+            self.level -= leaked;
+            self.timer += self.rate.calculate_time_for_tokens(leaked);
+        }
+    }
+
+    pub fn add(&mut self, tokens: u64, now: &LocalDateTime) {
+        self.level = self.level.saturating_add(tokens);
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+    }
+
+    /// Takes `tokens` back out of the bucket. Expects a leaked state.
+    pub fn refund(&mut self, tokens: u64) {
+        self.level = self.level.saturating_sub(tokens);
+    }
+
+    pub fn get_available_tokens(&self) -> u64 {
+        self.capacity.saturating_sub(self.level)
+    }
+
+    /// Returns the milliseconds until `tokens` tokens fit in the bucket. Expects a leaked state.
+    pub fn calculate_time_for_tokens(&self, tokens: u64, now: &LocalDateTime) -> i64 {
+        let target = self.capacity.saturating_sub(tokens);
+
+        self.time_until_level(target, now)
+    }
+
+    /// Returns the milliseconds until every queued token has leaked.
+    pub fn time_until_empty(&self, now: &LocalDateTime) -> i64 {
+        self.time_until_level(0, now)
+    }
+
+    /// Returns the moment the bucket is empty again.
+    pub fn get_reset_time(&self, now: &LocalDateTime) -> LocalDateTime {
+        to_date_time(now.timestamp_millis() + self.time_until_empty(now))
+    }
+
+    fn time_until_level(&self, target: u64, now: &LocalDateTime) -> i64 {
+        if self.level <= target {
+            return 0;
+        }
+
+        let progress = now.timestamp_millis() - self.timer;
+        let leak_time = self.rate.calculate_time_for_tokens(self.level - target);
+
+        (leak_time - progress).max(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::conformance;
+    use crate::storage::InMemoryStorage;
+    use crate::{Duration, MockClock};
+
+    #[test]
+    fn leaky_bucket_policy_conforms() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = LeakyBucketPolicy::new(
+            5,
+            "key".into(),
+            Rate::new(Duration::seconds(12), 1),
+            &mut storage,
+        )
+        .unwrap()
+        .with_clock(clock.clone());
+
+        // A full bucket is accepted at once, then drains another 5 within the interval.
+        conformance::run(
+            &mut policy,
+            &clock,
+            5,
+            Duration::minutes(1),
+            10,
+            Duration::minutes(1),
+        );
+    }
+
+    #[test]
+    fn paces_accepted_requests() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = LeakyBucketPolicy::new(3, "key".into(), Rate::per_second(1), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+
+        let times: Vec<_> = (0..3)
+            .map(|_| policy.consume(1).unwrap().time_to_act)
+            .collect();
+
+        assert_eq!(
+            times,
+            vec![
+                start,
+                start + Duration::seconds(1),
+                start + Duration::seconds(2)
+            ]
+        );
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        clock.advance(Duration::seconds(1));
+        let reservation = policy.consume(1).unwrap();
+        assert!(reservation.rate_limit.is_accepted());
+        assert_eq!(reservation.time_to_act, start + Duration::seconds(3));
+    }
+}
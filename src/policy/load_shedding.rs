@@ -0,0 +1,135 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::Policy;
+use crate::random::{RandomSource, XorShiftRandom};
+use crate::{LocalDateTime, Reservation};
+
+/// Drops a growing share of requests as the inner policy nears its limit, instead
+/// of accepting everything up to the limit and then rejecting everything.
+///
+/// Below `shed_from`, the share of the limit in use, every request goes to the
+/// inner policy. From there the chance of dropping a request grows linearly with
+/// the usage, up to every request once the limit is used up. Like random early
+/// detection in routers, this smooths the traffic at the edge of the limit.
+///
+/// Dropped requests are rejected with a
+/// [`ReserveError::MaxWaitDurationExceededError`] without counting against the
+/// limit. Reservations without a maximum wait are never dropped.
+pub struct LoadSheddingPolicy<P: Policy> {
+    inner: P,
+    shed_from: f64,
+    random: Box<dyn RandomSource>,
+}
+
+impl<P: Policy> Policy for LoadSheddingPolicy<P> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens == 0 || max_time.is_none() {
+            return self.inner.reserve(tokens, max_time);
+        }
+
+        let mut rate_limit = self.inner.peek()?;
+
+        if self.random.next_f64()
+            < self.drop_probability(rate_limit.limit, rate_limit.available_tokens)
+        {
+            rate_limit.accepted = false;
+
+            return Err(ReserveError::MaxWaitDurationExceededError { rate_limit });
+        }
+
+        self.inner.reserve(tokens, max_time)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        self.inner.refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.inner.now()
+    }
+}
+
+impl<P: Policy> LoadSheddingPolicy<P> {
+    /// `shed_from` is the share of the limit in use from which requests are
+    /// dropped, between `0.0` and `1.0`.
+    pub fn new(inner: P, shed_from: f64) -> Result<Self, PolicyError> {
+        if !(0. ..=1.).contains(&shed_from) {
+            return Err(PolicyError::InvalidFractionError);
+        }
+
+        Ok(Self {
+            inner,
+            shed_from,
+            random: Box::new(XorShiftRandom::new()),
+        })
+    }
+
+    /// Replaces the source of the random numbers, e.g. with a seeded one in tests.
+    pub fn with_random<R: RandomSource + 'static>(mut self, random: R) -> Self {
+        self.random = Box::new(random);
+        self
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn drop_probability(&self, limit: u64, available_tokens: u64) -> f64 {
+        if limit == 0 || self.shed_from >= 1. {
+            return 0.;
+        }
+
+        let usage = 1. - available_tokens.min(limit) as f64 / limit as f64;
+
+        ((usage - self.shed_from) / (1. - self.shed_from)).clamp(0., 1.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FixedWindowPolicy;
+    use crate::storage::InMemoryStorage;
+    use crate::Duration;
+
+    #[test]
+    fn sheds_only_past_the_threshold() {
+        let mut storage = InMemoryStorage::new();
+        let inner =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage).unwrap();
+        let mut policy = LoadSheddingPolicy::new(inner, 0.5).unwrap();
+
+        for _ in 0..6 {
+            assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        }
+
+        assert!((policy.drop_probability(10, 4) - 0.2).abs() < 1e-9);
+        assert_eq!(policy.drop_probability(10, 0), 1.);
+        assert!(LoadSheddingPolicy::new(policy.into_inner(), 1.5).is_err());
+    }
+
+    #[test]
+    fn seeded_shedding_drops_requests_before_the_limit() {
+        let outcomes = |seed| {
+            let mut storage = InMemoryStorage::new();
+            let inner =
+                FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                    .unwrap();
+            let mut policy = LoadSheddingPolicy::new(inner, 0.)
+                .unwrap()
+                .with_random(XorShiftRandom::from_seed(seed));
+
+            (0..10)
+                .map(|_| policy.consume(1).unwrap().rate_limit.is_accepted())
+                .collect::<Vec<_>>()
+        };
+
+        let first = outcomes(7);
+        assert_eq!(first, outcomes(7));
+        assert!(first[0]);
+        assert!(first.contains(&false));
+    }
+}
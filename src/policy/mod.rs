@@ -1,23 +1,369 @@
+mod adaptive;
+mod bucketed_sliding_window;
+mod combinator;
+mod compound;
+mod concurrency;
+#[cfg(any(test, feature = "test-util"))]
+pub mod conformance;
+mod const_fixed_window;
 mod fixed_window;
+mod hierarchical;
+mod interval_throttle;
+mod leaky_bucket;
+mod load_shedding;
+mod no_limit;
+mod quota;
+mod rate;
+mod rejection_cache;
+mod retry_after;
+mod sampled;
+mod simulation;
+mod sliding_log;
 mod sliding_window;
+mod threshold;
+mod token_bucket;
+pub mod vectors;
+mod warm_up;
 
 use crate::error::ReserveError;
-use crate::storage::Storage;
-use crate::Reservation;
+use crate::{Duration, LocalDateTime, LocalTime, RateLimit, Reservation};
 
-pub use fixed_window::{FixedWindowPolicy, FixedWindowState};
+pub use adaptive::AdaptivePolicy;
+pub use bucketed_sliding_window::{BucketedSlidingWindowPolicy, BucketedSlidingWindowState};
+pub use combinator::{And, Fallback, Or};
+pub use compound::CompoundPolicy;
+pub use concurrency::{ConcurrencyGuard, ConcurrencyPolicy, ConcurrencyState};
+pub use const_fixed_window::ConstFixedWindowPolicy;
+pub use fixed_window::{FixedWindowPolicy, FixedWindowState, WindowAlignment};
+pub use hierarchical::HierarchicalPolicy;
+pub use interval_throttle::{IntervalThrottlePolicy, IntervalThrottleState};
+pub use leaky_bucket::{LeakyBucketPolicy, LeakyBucketState};
+pub use load_shedding::LoadSheddingPolicy;
+pub use no_limit::NoLimitPolicy;
+pub use quota::{QuotaPeriod, QuotaPolicy, QuotaState, QuotaTimeZone};
+pub use rate::Rate;
+pub use rejection_cache::RejectionCachePolicy;
+pub use retry_after::{RetryAfterPolicy, RetryAfterRounding};
+pub use sampled::SampledPolicy;
+pub use simulation::{replay, simulate, RecordedDecision, Replay, Simulation};
+pub use sliding_log::{SlidingLogPolicy, SlidingLogState};
 pub use sliding_window::{SlidingWindowPolicy, SlidingWindowState};
+pub use threshold::{ThresholdCrossing, ThresholdPolicy};
+pub use token_bucket::{TokenBucketPolicy, TokenBucketState};
+pub use warm_up::WarmUpPolicy;
+
+/// How many times the limit the hit count of a window may grow to.
+///
+/// Reservations over the limit keep adding hits, so the count is capped instead
+/// of wrapping around into available tokens. Past the cap, further reservations
+/// no longer push the retry time back.
+pub const HIT_COUNT_OVERFLOW_FACTOR: u64 = 4;
+
+/// Called with the key whenever its hit count is capped at
+/// [`HIT_COUNT_OVERFLOW_FACTOR`] times the limit, e.g. to log or count the event.
+pub type CapHook = Box<dyn FnMut(&str) + Send>;
+
+/// Scales `hits` counted against a limit of `from` to a limit of `to`, so the
+/// same share of the limit stays used. Rounds up so rescaling never frees tokens.
+pub(crate) fn rescale_hits(hits: u64, from: u64, to: u64) -> u64 {
+    if from == 0 {
+        return hits;
+    }
+
+    let scaled = (u128::from(hits) * u128::from(to)).div_ceil(u128::from(from));
+
+    u64::try_from(scaled).unwrap_or(u64::MAX)
+}
 
 pub trait Policy {
-    // reset
     // consume(tokens = 1)
     // reserve(tokens = 1, float maxTime = null)
 
-    fn reserve(
+    /// Reserves `tokens`, waiting at most `max_time` milliseconds for them.
+    ///
+    /// Reserving zero tokens records nothing and reports the current rate limit,
+    /// which is what [`Self::peek()`] does.
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError>;
+
+    /// Same as [`Self::reserve()`], waiting at most `max_wait` for the tokens.
+    fn reserve_within(
+        &mut self,
+        tokens: u64,
+        max_wait: Duration,
+    ) -> Result<Reservation, ReserveError> {
+        self.reserve(tokens, Some(max_wait.num_milliseconds()))
+    }
+
+    /// Same as [`Self::reserve()`], rejecting the reservation with a
+    /// [`ReserveError::MaxWaitDurationExceededError`] when it would act after `deadline`.
+    ///
+    /// The wait is measured from [`Self::now()`], so request-scoped deadlines
+    /// need no conversion to a relative wait at the call site.
+    fn reserve_until(
+        &mut self,
+        tokens: u64,
+        deadline: LocalDateTime,
+    ) -> Result<Reservation, ReserveError> {
+        let max_time = (deadline - self.now()).num_milliseconds();
+
+        if max_time < 0 {
+            let mut rate_limit = self.peek()?;
+            rate_limit.accepted = false;
+
+            return Err(ReserveError::MaxWaitDurationExceededError { rate_limit });
+        }
+
+        self.reserve(tokens, Some(max_time))
+    }
+
+    /// Returns the remaining tokens and the retry time without recording anything.
+    fn peek(&mut self) -> Result<RateLimit, ReserveError> {
+        Ok(self.reserve(0, None)?.rate_limit)
+    }
+
+    /// Takes the tokens only if they are available right now.
+    ///
+    /// Unlike [`Self::reserve()`], a rejected consume does not count against the limit.
+    /// The returned reservation is then not accepted and acts at the retry time.
+    fn consume(&mut self, tokens: u64) -> Result<Reservation, ReserveError> {
+        match self.reserve(tokens, Some(0)) {
+            Err(ReserveError::MaxWaitDurationExceededError { rate_limit }) => Ok(Reservation {
+                time_to_act: rate_limit.retry_after,
+                rate_limit,
+            }),
+            result => result,
+        }
+    }
+
+    /// Counts `tokens` extra hits against the limit without reserving them, e.g. to
+    /// charge a client for an abusive or failed request.
+    ///
+    /// The default implementation reserves the tokens and drops the reservation,
+    /// so it fails like [`Self::reserve()`] when `tokens` exceeds the limit. The
+    /// window policies add the hits directly and accept any amount.
+    fn penalize(&mut self, tokens: u64) -> Result<(), ReserveError> {
+        self.reserve(tokens, None).map(|_| ())
+    }
+
+    /// Gives back `tokens` taken by an earlier reservation, e.g. when the protected
+    /// operation was not performed because the downstream service failed.
+    ///
+    /// Refunds never raise the available tokens above the limit, and tokens whose
+    /// window or period has already ended are not given back. The default
+    /// implementation ignores refunds, for policies that cannot tell which inner
+    /// policy took the tokens.
+    fn refund(&mut self, _tokens: u64) {}
+
+    /// Clears the stored state of the key, making the full limit available right away,
+    /// e.g. to unblock a user from admin tooling.
+    ///
+    /// The default implementation does nothing, for policies without stored state.
+    fn reset(&mut self) {}
+
+    /// Reserves `tokens_per_slot` tokens for each of `slots` consecutive slots,
+    /// spaced at least `spacing` apart starting from now.
+    ///
+    /// Each reservation acts no earlier than the policy allows and no earlier than
+    /// its slot, so the last reservation's time to act is the ETA of the whole run.
+    /// If a slot cannot be reserved, the error is returned and the slots reserved
+    /// before it stay reserved.
+    fn reserve_schedule(
+        &mut self,
+        tokens_per_slot: u64,
+        slots: usize,
+        spacing: Duration,
+    ) -> Result<Vec<Reservation>, ReserveError> {
+        let mut reservations = Vec::with_capacity(slots);
+        let mut slot_start = self.reserve(0, None)?.time_to_act;
+
+        for _ in 0..slots {
+            let mut reservation = self.reserve(tokens_per_slot, None)?;
+
+            if reservation.time_to_act < slot_start {
+                reservation.time_to_act = slot_start;
+            }
+
+            reservations.push(reservation);
+            slot_start += spacing;
+        }
+
+        Ok(reservations)
+    }
+
+    /// Returns the current time as read by the policy.
+    ///
+    /// The default implementation reads the system time. Policies built with a
+    /// clock return the time of that clock, wrappers the time of their inner policy.
+    fn now(&self) -> LocalDateTime {
+        LocalTime::now()
+    }
+
+    /// Combines this policy with `other` so a request is accepted only if both accept it.
+    fn and<P: Policy>(self, other: P) -> And<Self, P>
+    where
+        Self: Sized,
+    {
+        combinator::and(self, other)
+    }
+
+    /// Combines this policy with `other` so a request is accepted if either accepts it.
+    fn or<P: Policy>(self, other: P) -> Or<Self, P>
+    where
+        Self: Sized,
+    {
+        combinator::or(self, other)
+    }
+
+    /// Hands requests over to `other` when this policy fails to handle them.
+    fn fallback<P: Policy>(self, other: P) -> Fallback<Self, P>
+    where
+        Self: Sized,
+    {
+        combinator::fallback(self, other)
+    }
+
+    /// Boxes this policy so policies of different types can be picked at runtime
+    /// or stored together, e.g. in a map from configuration names to policies.
+    fn boxed<'a>(self) -> BoxedPolicy<'a>
+    where
+        Self: Sized + 'a,
+    {
+        Box::new(self)
+    }
+}
+
+/// A policy of any type, see [`Policy::boxed()`].
+pub type BoxedPolicy<'a> = Box<dyn Policy + 'a>;
+
+impl<P: Policy + ?Sized> Policy for Box<P> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        (**self).reserve(tokens, max_time)
+    }
+
+    fn peek(&mut self) -> Result<RateLimit, ReserveError> {
+        (**self).peek()
+    }
+
+    fn now(&self) -> LocalDateTime {
+        (**self).now()
+    }
+
+    fn consume(&mut self, tokens: u64) -> Result<Reservation, ReserveError> {
+        (**self).consume(tokens)
+    }
+
+    fn penalize(&mut self, tokens: u64) -> Result<(), ReserveError> {
+        (**self).penalize(tokens)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        (**self).refund(tokens)
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+
+    fn reserve_schedule(
         &mut self,
-        tokens: usize,
-        max_time: Option<i64>,
-    ) -> Result<Reservation, ReserveError>;
+        tokens_per_slot: u64,
+        slots: usize,
+        spacing: Duration,
+    ) -> Result<Vec<Reservation>, ReserveError> {
+        (**self).reserve_schedule(tokens_per_slot, slots, spacing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::{Clock, MockClock};
+
+    #[test]
+    fn reserve_schedule_spaces_slots() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock);
+
+        let schedule = policy.reserve_schedule(5, 3, Duration::minutes(1)).unwrap();
+
+        assert_eq!(schedule.len(), 3);
+        assert_eq!(schedule[0].time_to_act, start);
+        assert_eq!(schedule[1].time_to_act, start + Duration::minutes(1));
+        assert!(schedule[2].time_to_act >= start + Duration::minutes(1));
+    }
+
+    #[test]
+    fn reserve_within_bounds_the_wait() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock);
+
+        policy.consume(1).unwrap();
+        assert!(policy.reserve_within(1, Duration::seconds(30)).is_err());
+        assert!(policy.reserve_within(1, Duration::minutes(1)).is_ok());
+    }
+
+    #[test]
+    fn reserve_until_rejects_past_the_deadline() {
+        let clock = MockClock::default();
+        let now = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock);
+
+        assert!(policy.reserve_until(1, now - Duration::seconds(1)).is_err());
+        assert!(policy.reserve_until(1, now).is_ok());
+        assert!(policy
+            .reserve_until(1, now + Duration::seconds(30))
+            .is_err());
+        assert!(policy.reserve_until(1, now + Duration::minutes(1)).is_ok());
+    }
+
+    #[test]
+    fn boxed_policies_are_picked_at_runtime() {
+        let (mut fixed, mut tokens) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let mut policies: std::collections::HashMap<&str, BoxedPolicy> = [
+            (
+                "fixed",
+                FixedWindowPolicy::new(2, "key".into(), Duration::minutes(1), &mut fixed)
+                    .unwrap()
+                    .boxed(),
+            ),
+            (
+                "token",
+                TokenBucketPolicy::new(3, "key".into(), Rate::per_minute(3), &mut tokens)
+                    .unwrap()
+                    .boxed(),
+            ),
+        ]
+        .into();
+
+        assert_eq!(
+            policies
+                .get_mut("fixed")
+                .unwrap()
+                .peek()
+                .unwrap()
+                .get_limit(),
+            2
+        );
 
-    fn consume(&mut self, tokens: usize) -> Result<Reservation, ReserveError>;
+        // Boxed policies still compose.
+        let mut both = policies
+            .remove("fixed")
+            .unwrap()
+            .and(policies.remove("token").unwrap());
+        assert_eq!(both.consume(2).unwrap().rate_limit.get_limit(), 2);
+    }
 }
@@ -0,0 +1,82 @@
+use crate::error::ReserveError;
+use crate::policy::Policy;
+use crate::{Clock, LocalDateTime, RateLimit, Reservation, SystemClock, Unit};
+
+/// Accepts every request and never stores anything.
+///
+/// Use it in place of a real policy to disable rate limiting, e.g. in development
+/// environments, without changing the call sites. It always reports `u64::MAX`
+/// available tokens.
+pub struct NoLimitPolicy {
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl Policy for NoLimitPolicy {
+    fn reserve(
+        &mut self,
+        _tokens: u64,
+        _max_time: Option<i64>,
+    ) -> Result<Reservation, ReserveError> {
+        let now = self.clock.now();
+
+        Ok(Reservation {
+            time_to_act: now,
+            rate_limit: RateLimit {
+                available_tokens: u64::MAX,
+                retry_after: now,
+                accepted: true,
+                limit: u64::MAX,
+                reset_at: now,
+                unit: self.unit.clone(),
+            },
+        })
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl NoLimitPolicy {
+    pub fn new() -> Self {
+        Self {
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+}
+
+impl Default for NoLimitPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn always_accepts() {
+        let mut policy = NoLimitPolicy::new();
+
+        for _ in 0..1_000 {
+            let rate_limit = policy.consume(u64::MAX).unwrap().rate_limit;
+            assert!(rate_limit.is_accepted());
+            assert_eq!(rate_limit.get_remaining_tokens(), u64::MAX);
+        }
+    }
+}
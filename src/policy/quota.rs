@@ -0,0 +1,435 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::{Policy, HIT_COUNT_OVERFLOW_FACTOR};
+use crate::storage::{State, Storage};
+use crate::{
+    ChronoTimestampMillis, Clock, LocalDateTime, LocalTime, RateLimit, Reservation, SystemClock,
+    Unit,
+};
+use chrono::{Datelike, Days, Months, NaiveDate, TimeZone, Utc};
+
+/// A calendar period a [`QuotaPolicy`] counts tokens over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaPeriod {
+    /// From midnight to midnight.
+    Day,
+    /// From Monday midnight to the next Monday midnight.
+    Week,
+    /// From midnight on the first of the month to the first of the next month.
+    Month,
+}
+
+/// The time zone whose midnight starts the periods of a [`QuotaPolicy`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaTimeZone {
+    #[default]
+    Local,
+    Utc,
+}
+
+/// Allows `limit` tokens per calendar day, week or month, e.g. "10,000 calls per month".
+///
+/// Unlike [`crate::policy::FixedWindowPolicy`], whose window is a fixed number of
+/// milliseconds, the periods follow the calendar: months have their actual length
+/// and periods start at midnight in the configured time zone.
+pub struct QuotaPolicy<'a, Store: Storage<QuotaState, QuotaState>> {
+    limit: u64,
+    key: String,
+    period: QuotaPeriod,
+    time_zone: QuotaTimeZone,
+    max_carry_over: Option<u64>,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl<Store: Storage<QuotaState, QuotaState>> Policy for QuotaPolicy<'_, Store> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens > self.limit {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.limit,
+            });
+        }
+
+        let now = self.clock.now();
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| QuotaState::new(self.key.clone()));
+
+        if state.is_expired(&now) {
+            let (start, end) = period_bounds(self.period, self.time_zone, &now);
+            // Only a period directly following the previous one gets its unused tokens.
+            let carried_over = match self.max_carry_over {
+                Some(max_carry_over) if state.period_end == start => state
+                    .get_period_size(self.limit)
+                    .saturating_sub(state.hit_count)
+                    .min(max_carry_over),
+                _ => 0,
+            };
+
+            state.start_period(start, end);
+            state.carried_over = carried_over;
+        }
+
+        let period_size = state.get_period_size(self.limit);
+        let available_tokens = period_size.saturating_sub(state.hit_count);
+        let reset_at = state.get_reset_time();
+
+        let reservation = if tokens == 0 {
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens,
+                    retry_after: if available_tokens > 0 { now } else { reset_at },
+                    accepted: true,
+                    limit: self.limit,
+                    reset_at,
+                    unit: self.unit.clone(),
+                },
+            }
+        } else if available_tokens >= tokens {
+            state.add(tokens, period_size, &now);
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens: period_size.saturating_sub(state.hit_count),
+                    retry_after: now,
+                    accepted: true,
+                    limit: self.limit,
+                    reset_at,
+                    unit: self.unit.clone(),
+                },
+            }
+        } else {
+            let wait_duration = state.period_end - now.timestamp_millis();
+
+            if let Some(max_time) = max_time {
+                if wait_duration > max_time {
+                    return Err(ReserveError::MaxWaitDurationExceededError {
+                        rate_limit: RateLimit {
+                            available_tokens,
+                            retry_after: reset_at,
+                            accepted: false,
+                            limit: self.limit,
+                            reset_at,
+                            unit: self.unit.clone(),
+                        },
+                    });
+                }
+            }
+
+            state.add(tokens, period_size, &now);
+
+            Reservation {
+                time_to_act: reset_at,
+                rate_limit: RateLimit {
+                    available_tokens: 0,
+                    retry_after: reset_at,
+                    accepted: false,
+                    limit: self.limit,
+                    reset_at,
+                    unit: self.unit.clone(),
+                },
+            }
+        };
+
+        if tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            if !state.is_expired(&self.clock.now()) {
+                state.hit_count = state.hit_count.saturating_sub(tokens);
+                self.storage.save(&self.key, state);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<QuotaState, QuotaState>> QuotaPolicy<'a, Store> {
+    /// Allows `limit` tokens per calendar `period`, starting at local midnight.
+    pub fn new(
+        limit: u64,
+        key: String,
+        period: QuotaPeriod,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        Ok(Self {
+            limit,
+            key,
+            period,
+            time_zone: QuotaTimeZone::default(),
+            max_carry_over: None,
+            storage,
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Sets the time zone whose midnight starts the periods.
+    pub fn with_time_zone(mut self, time_zone: QuotaTimeZone) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    /// Carries the unused tokens of a period, up to `max_carry_over`, into the
+    /// next one, e.g. to roll unused daily quota over to the next day.
+    ///
+    /// Only the tokens left at the end of the previous period carry over, so a
+    /// key unused for several periods does not bank the quota of all of them.
+    pub fn with_carry_over(mut self, max_carry_over: u64) -> Self {
+        self.max_carry_over = Some(max_carry_over);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+}
+
+/// Returns the start and the end of the period containing `now`, in timestamp millis.
+fn period_bounds(
+    period: QuotaPeriod,
+    time_zone: QuotaTimeZone,
+    now: &LocalDateTime,
+) -> (ChronoTimestampMillis, ChronoTimestampMillis) {
+    let today = match time_zone {
+        QuotaTimeZone::Local => now.date_naive(),
+        QuotaTimeZone::Utc => now.with_timezone(&Utc).date_naive(),
+    };
+
+    let (start, end) = match period {
+        QuotaPeriod::Day => (today, today + Days::new(1)),
+        QuotaPeriod::Week => {
+            let monday = today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+            (monday, monday + Days::new(7))
+        }
+        QuotaPeriod::Month => {
+            let first = today.with_day(1).unwrap();
+            (first, first + Months::new(1))
+        }
+    };
+
+    (
+        midnight(start, time_zone).unwrap_or(now.timestamp_millis()),
+        midnight(end, time_zone).unwrap_or(i64::MAX),
+    )
+}
+
+fn midnight(date: NaiveDate, time_zone: QuotaTimeZone) -> Option<ChronoTimestampMillis> {
+    let midnight = date.and_hms_opt(0, 0, 0)?;
+
+    match time_zone {
+        // A midnight skipped by a daylight saving change starts at the next valid time,
+        // so fall back to the earliest mapping an hour later.
+        QuotaTimeZone::Local => LocalTime
+            .from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| {
+                LocalTime
+                    .from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|midnight| midnight.timestamp_millis()),
+        QuotaTimeZone::Utc => Some(Utc.from_utc_datetime(&midnight).timestamp_millis()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuotaState {
+    pub key: String,
+    pub hit_count: u64,
+    pub period_start: ChronoTimestampMillis,
+    pub period_end: ChronoTimestampMillis,
+    /// Unused tokens of the previous period available on top of the limit, used by the carry-over.
+    pub carried_over: u64,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+}
+
+impl State<QuotaState> for QuotaState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.period_end.saturating_sub(self.period_start)).unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
+    }
+}
+
+impl QuotaState {
+    pub fn new(key: String) -> Self {
+        Self {
+            key,
+            hit_count: 0,
+            period_start: 0,
+            period_end: 0,
+            carried_over: 0,
+            created_at: None,
+            last_hit_at: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: &LocalDateTime) -> bool {
+        now.timestamp_millis() >= self.period_end
+    }
+
+    pub fn start_period(&mut self, start: ChronoTimestampMillis, end: ChronoTimestampMillis) {
+        self.period_start = start;
+        self.period_end = end;
+        self.hit_count = 0;
+        self.carried_over = 0;
+    }
+
+    /// Returns the tokens the current period allows, including the carried over tokens.
+    pub fn get_period_size(&self, limit: u64) -> u64 {
+        limit.saturating_add(self.carried_over)
+    }
+
+    /// Adds `hits` to the period, capping the hit count at
+    /// [`HIT_COUNT_OVERFLOW_FACTOR`] times `period_size`, the tokens the period allows
+    /// including the carried over ones. Returns true when the cap was hit.
+    pub fn add(&mut self, hits: u64, period_size: u64, now: &LocalDateTime) -> bool {
+        let max_hit_count = period_size.saturating_mul(HIT_COUNT_OVERFLOW_FACTOR);
+        let hit_count = self.hit_count.saturating_add(hits);
+
+        self.hit_count = hit_count.min(max_hit_count);
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+
+        hit_count > max_hit_count
+    }
+
+    /// Returns the moment the current period ends.
+    pub fn get_reset_time(&self) -> LocalDateTime {
+        LocalTime.timestamp_millis_opt(self.period_end).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::{Duration, MockClock};
+
+    #[test]
+    fn monthly_quota_resets_on_the_first() {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 2, 28, 12, 0, 0).unwrap().into());
+        let mut storage = InMemoryStorage::new();
+        let mut policy = QuotaPolicy::new(2, "key".into(), QuotaPeriod::Month, &mut storage)
+            .unwrap()
+            .with_time_zone(QuotaTimeZone::Utc)
+            .with_clock(clock.clone());
+
+        assert!(policy.consume(2).unwrap().rate_limit.is_accepted());
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(
+            rejected.get_retry_after(),
+            Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+        );
+
+        clock.advance(Duration::hours(36));
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn unused_daily_quota_rolls_over_up_to_the_cap() {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap().into());
+        let mut storage = InMemoryStorage::new();
+        let mut policy = QuotaPolicy::new(10, "key".into(), QuotaPeriod::Day, &mut storage)
+            .unwrap()
+            .with_time_zone(QuotaTimeZone::Utc)
+            .with_carry_over(5)
+            .with_clock(clock.clone());
+
+        policy.consume(2).unwrap();
+
+        clock.advance(Duration::days(1));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 15);
+        assert!(policy.consume(10).unwrap().rate_limit.is_accepted());
+        assert!(policy.consume(5).unwrap().rate_limit.is_accepted());
+
+        // Skipping a day loses the tokens carried so far.
+        clock.advance(Duration::days(2));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 10);
+    }
+
+    #[test]
+    fn sustained_load_exhausts_a_large_carried_over_quota() {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap().into());
+        let mut storage = InMemoryStorage::new();
+        let mut policy = QuotaPolicy::new(10, "key".into(), QuotaPeriod::Day, &mut storage)
+            .unwrap()
+            .with_time_zone(QuotaTimeZone::Utc)
+            .with_carry_over(100)
+            .with_clock(clock.clone());
+
+        for _ in 0..15 {
+            policy.consume(1).unwrap();
+            clock.advance(Duration::days(1));
+        }
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 110);
+
+        let accepted = (0..200)
+            .filter(|_| policy.consume(1).unwrap().rate_limit.is_accepted())
+            .count();
+        assert_eq!(accepted, 110);
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // 2024-01-03 is a Wednesday.
+        let now: LocalDateTime = Utc.with_ymd_and_hms(2024, 1, 3, 8, 0, 0).unwrap().into();
+        let (start, end) = period_bounds(QuotaPeriod::Week, QuotaTimeZone::Utc, &now);
+
+        assert_eq!(
+            start,
+            Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0)
+                .unwrap()
+                .timestamp_millis()
+        );
+        assert_eq!(end - start, Duration::days(7).num_milliseconds());
+    }
+}
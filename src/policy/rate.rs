@@ -0,0 +1,51 @@
+use crate::{ChronoTimestampMillis, Duration};
+
+/// Rate at which a bucket refills or drains: `refill_amount` tokens every `refill_time`.
+#[derive(Debug, Clone, Copy)]
+pub struct Rate {
+    refill_time: ChronoTimestampMillis,
+    refill_amount: u64,
+}
+
+impl Rate {
+    pub fn new(refill_time: Duration, refill_amount: u64) -> Self {
+        Self {
+            refill_time: refill_time.num_milliseconds(),
+            refill_amount,
+        }
+    }
+
+    pub fn per_second(refill_amount: u64) -> Self {
+        Self::new(Duration::seconds(1), refill_amount)
+    }
+
+    pub fn per_minute(refill_amount: u64) -> Self {
+        Self::new(Duration::minutes(1), refill_amount)
+    }
+
+    pub fn per_hour(refill_amount: u64) -> Self {
+        Self::new(Duration::hours(1), refill_amount)
+    }
+
+    pub fn per_day(refill_amount: u64) -> Self {
+        Self::new(Duration::days(1), refill_amount)
+    }
+
+    /// Returns the milliseconds needed to refill `tokens` tokens.
+    pub fn calculate_time_for_tokens(&self, tokens: u64) -> i64 {
+        let cycles = i64::try_from(tokens.div_ceil(self.refill_amount)).unwrap_or(i64::MAX);
+
+        cycles.saturating_mul(self.refill_time)
+    }
+
+    /// Returns the number of tokens refilled during `elapsed` milliseconds.
+    pub fn calculate_new_tokens_during_interval(&self, elapsed: i64) -> u64 {
+        let cycles = u64::try_from(elapsed / self.refill_time).unwrap_or(0);
+
+        cycles.saturating_mul(self.refill_amount)
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        self.refill_time > 0 && self.refill_amount > 0
+    }
+}
@@ -0,0 +1,125 @@
+use crate::error::ReserveError;
+use crate::policy::Policy;
+use crate::{LocalDateTime, RateLimit, Reservation};
+
+/// Serves repeated rejections from memory until the known retry time has passed.
+///
+/// Once the inner policy rejects a request, requests for at least as many tokens
+/// that cannot wait until the retry time are rejected right away, without asking
+/// the inner policy and so without touching its storage. This keeps a client
+/// retrying in a tight loop from hammering a remote storage.
+///
+/// The cache is local to this wrapper: tokens freed early by other processes are
+/// only noticed once the cached retry time has passed.
+pub struct RejectionCachePolicy<P: Policy> {
+    inner: P,
+    rejection: Option<(u64, RateLimit)>,
+}
+
+impl<P: Policy> Policy for RejectionCachePolicy<P> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        let now = self.inner.now();
+
+        if let (Some((rejected_tokens, rate_limit)), Some(max_time)) = (&self.rejection, max_time) {
+            let wait_duration = (rate_limit.retry_after - now).num_milliseconds();
+
+            if wait_duration <= 0 {
+                self.rejection = None;
+            } else if tokens >= *rejected_tokens && wait_duration > max_time {
+                return Err(ReserveError::MaxWaitDurationExceededError {
+                    rate_limit: rate_limit.clone(),
+                });
+            }
+        }
+
+        match self.inner.reserve(tokens, max_time) {
+            Err(ReserveError::MaxWaitDurationExceededError { rate_limit }) => {
+                self.rejection = Some((tokens, rate_limit.clone()));
+
+                Err(ReserveError::MaxWaitDurationExceededError { rate_limit })
+            }
+            result => result,
+        }
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        // Refunded tokens may serve the cached rejection before its retry time.
+        self.rejection = None;
+        self.inner.refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.rejection = None;
+        self.inner.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.inner.now()
+    }
+}
+
+impl<P: Policy> RejectionCachePolicy<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            rejection: None,
+        }
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FixedWindowPolicy, FixedWindowState};
+    use crate::storage::{InMemoryStorage, Storage};
+    use crate::{Duration, MockClock};
+    use std::cell::Cell;
+
+    struct CountingStorage {
+        inner: InMemoryStorage<FixedWindowState, FixedWindowState>,
+        fetches: Cell<usize>,
+    }
+
+    impl Storage<FixedWindowState, FixedWindowState> for CountingStorage {
+        fn fetch(&self, key: &str) -> Option<FixedWindowState> {
+            self.fetches.set(self.fetches.get() + 1);
+            self.inner.fetch(key)
+        }
+
+        fn save<IntoString: Into<String>>(&mut self, key: IntoString, value: FixedWindowState) {
+            self.inner.save(key, value);
+        }
+
+        fn delete(&mut self, key: &str) {
+            self.inner.delete(key);
+        }
+    }
+
+    #[test]
+    fn serves_rejections_without_storage_access() {
+        let clock = MockClock::default();
+        let mut storage = CountingStorage {
+            inner: InMemoryStorage::new(),
+            fetches: Cell::new(0),
+        };
+        let inner = FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut policy = RejectionCachePolicy::new(inner);
+
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+        for _ in 0..10 {
+            assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+        }
+
+        clock.advance(Duration::minutes(1));
+        assert!(policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        drop(policy);
+        assert_eq!(storage.fetches.get(), 3);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::error::ReserveError;
+use crate::policy::Policy;
+use crate::random::{RandomSource, XorShiftRandom};
+use crate::{Duration, LocalDateTime, RateLimit, Reservation};
+
+/// How [`RetryAfterPolicy`] rounds the advertised wait.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RetryAfterRounding {
+    /// Keeps the millisecond precision of the inner policy.
+    #[default]
+    None,
+    /// Rounds the wait up to whole seconds, as sent in an HTTP `Retry-After` header.
+    CeilSeconds,
+}
+
+/// Rounds and clamps the retry time the inner policy advertises to rejected requests.
+///
+/// Raw millisecond retry times leak the internals of the windows and confuse
+/// clients, so the wait until the retry time can be rounded up to whole seconds
+/// and kept between a minimum and a maximum. Only rejected requests are affected,
+/// and only the advertised retry time changes: the time to act of a reservation
+/// still is the one of the inner policy.
+///
+/// A random jitter can be added to the wait so that many throttled clients do
+/// not all retry at the same millisecond. It is added before the rounding and
+/// the clamping.
+pub struct RetryAfterPolicy<P: Policy> {
+    inner: P,
+    rounding: RetryAfterRounding,
+    min_wait: Option<Duration>,
+    max_wait: Option<Duration>,
+    jitter: Option<Duration>,
+    random: Box<dyn RandomSource>,
+}
+
+impl<P: Policy> Policy for RetryAfterPolicy<P> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        match self.inner.reserve(tokens, max_time) {
+            Ok(mut reservation) => {
+                self.adjust(&mut reservation.rate_limit);
+                Ok(reservation)
+            }
+            Err(ReserveError::MaxWaitDurationExceededError { mut rate_limit }) => {
+                self.adjust(&mut rate_limit);
+                Err(ReserveError::MaxWaitDurationExceededError { rate_limit })
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        self.inner.refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.inner.now()
+    }
+}
+
+impl<P: Policy> RetryAfterPolicy<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            rounding: RetryAfterRounding::default(),
+            min_wait: None,
+            max_wait: None,
+            jitter: None,
+            random: Box::new(XorShiftRandom::new()),
+        }
+    }
+
+    /// Sets how the advertised wait is rounded.
+    pub fn with_rounding(mut self, rounding: RetryAfterRounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Never advertises a wait shorter than `min_wait` to a rejected request.
+    pub fn with_min_wait(mut self, min_wait: Duration) -> Self {
+        self.min_wait = Some(min_wait);
+        self
+    }
+
+    /// Never advertises a wait longer than `max_wait`, even if the tokens come back later.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// Adds a random wait between zero and `jitter` to the advertised wait.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// Replaces the source of the random numbers, e.g. with a seeded one in tests.
+    pub fn with_random<R: RandomSource + 'static>(mut self, random: R) -> Self {
+        self.random = Box::new(random);
+        self
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn adjust(&mut self, rate_limit: &mut RateLimit) {
+        if rate_limit.accepted {
+            return;
+        }
+
+        let now = self.inner.now();
+        let mut wait = (rate_limit.retry_after - now).max(Duration::zero());
+
+        if let Some(jitter) = self.jitter {
+            let jitter = jitter.num_milliseconds().max(0) as f64 * self.random.next_f64();
+            wait += Duration::milliseconds(jitter as i64);
+        }
+
+        if self.rounding == RetryAfterRounding::CeilSeconds {
+            let seconds = (wait.num_milliseconds() + 999).div_euclid(1_000);
+            wait = Duration::seconds(seconds);
+        }
+
+        if let Some(min_wait) = self.min_wait {
+            wait = wait.max(min_wait);
+        }
+
+        if let Some(max_wait) = self.max_wait {
+            wait = wait.min(max_wait);
+        }
+
+        rate_limit.retry_after = now + wait;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FixedWindowPolicy;
+    use crate::storage::InMemoryStorage;
+    use crate::{Clock, MockClock};
+
+    #[test]
+    fn rounds_and_clamps_the_advertised_wait() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let inner = FixedWindowPolicy::new(1, "key".into(), Duration::seconds(10), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut policy = RetryAfterPolicy::new(inner)
+            .with_rounding(RetryAfterRounding::CeilSeconds)
+            .with_min_wait(Duration::seconds(2))
+            .with_max_wait(Duration::seconds(5));
+
+        let accepted = policy.consume(1).unwrap().rate_limit;
+        assert_eq!(accepted.get_retry_after(), clock.now());
+
+        clock.advance(Duration::milliseconds(3_500));
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert_eq!(
+            rejected.get_retry_after(),
+            clock.now() + Duration::seconds(5)
+        );
+
+        clock.advance(Duration::milliseconds(5_600));
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert_eq!(
+            rejected.get_retry_after(),
+            clock.now() + Duration::seconds(2)
+        );
+
+        clock.advance(Duration::milliseconds(300));
+        let reservation = policy.reserve(1, None).unwrap();
+        assert_eq!(
+            reservation.rate_limit.get_retry_after(),
+            clock.now() + Duration::seconds(2)
+        );
+        assert_eq!(
+            *reservation.get_time_to_act(),
+            clock.now() + Duration::milliseconds(600)
+        );
+    }
+
+    #[test]
+    fn jitter_spreads_the_advertised_wait() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let inner = FixedWindowPolicy::new(1, "key".into(), Duration::seconds(10), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut policy = RetryAfterPolicy::new(inner).with_jitter(Duration::seconds(2));
+
+        policy.consume(1).unwrap();
+
+        for _ in 0..20 {
+            let retry_after = policy.consume(1).unwrap().rate_limit.get_retry_after();
+            let jitter = retry_after - (clock.now() + Duration::seconds(10));
+            assert!(jitter >= Duration::zero() && jitter <= Duration::seconds(2));
+        }
+    }
+
+    #[test]
+    fn seeded_jitter_repeats_itself() {
+        let retry_afters = |seed| {
+            let clock = MockClock::default();
+            let mut storage = InMemoryStorage::new();
+            let inner =
+                FixedWindowPolicy::new(1, "key".into(), Duration::seconds(10), &mut storage)
+                    .unwrap()
+                    .with_clock(clock.clone());
+            let mut policy = RetryAfterPolicy::new(inner)
+                .with_jitter(Duration::seconds(2))
+                .with_random(XorShiftRandom::from_seed(seed));
+
+            policy.consume(1).unwrap();
+            (0..5)
+                .map(|_| policy.consume(1).unwrap().rate_limit.get_retry_after() - clock.now())
+                .collect::<Vec<_>>()
+        };
+
+        let first = retry_afters(7);
+        assert_eq!(first, retry_afters(7));
+        assert_ne!(first, retry_afters(8));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::Policy;
+use crate::random::{RandomSource, XorShiftRandom};
+use crate::{LocalDateTime, Reservation};
+
+/// Enforces the inner policy only for a sampled fraction of requests.
+///
+/// Only sampled requests can be rejected; the others are always reported as
+/// accepted, even when they ask for more tokens than the limit. This allows
+/// rolling out a new limit gradually on high traffic endpoints.
+///
+/// Unsampled requests are consumed from the inner policy while it has room, so
+/// they count against the limit, but never queue a reservation: they do not
+/// push back the retry time of the sampled requests.
+pub struct SampledPolicy<P: Policy> {
+    inner: P,
+    fraction: f64,
+    random: Box<dyn RandomSource>,
+}
+
+impl<P: Policy> Policy for SampledPolicy<P> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if self.random.next_f64() < self.fraction {
+            return self.inner.reserve(tokens, max_time);
+        }
+
+        let (mut reservation, too_many_tokens) = match self.inner.consume(tokens) {
+            Err(ReserveError::TooManyTokensError { .. }) => {
+                let rate_limit = self.inner.peek()?;
+
+                (
+                    Reservation {
+                        time_to_act: rate_limit.retry_after,
+                        rate_limit,
+                    },
+                    true,
+                )
+            }
+            result => (result?, false),
+        };
+
+        if !reservation.rate_limit.accepted || too_many_tokens {
+            let now = self.inner.now();
+            reservation.time_to_act = now;
+            reservation.rate_limit.retry_after = now;
+            reservation.rate_limit.accepted = true;
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        self.inner.refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.inner.now()
+    }
+}
+
+impl<P: Policy> SampledPolicy<P> {
+    /// `fraction` is the share of requests the limit applies to, between `0.0` and `1.0`.
+    pub fn new(inner: P, fraction: f64) -> Result<Self, PolicyError> {
+        if !(0. ..=1.).contains(&fraction) {
+            return Err(PolicyError::InvalidFractionError);
+        }
+
+        Ok(Self {
+            inner,
+            fraction,
+            random: Box::new(XorShiftRandom::new()),
+        })
+    }
+
+    /// Replaces the source of the random numbers, e.g. with a seeded one in tests.
+    pub fn with_random<R: RandomSource + 'static>(mut self, random: R) -> Self {
+        self.random = Box::new(random);
+        self
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FixedWindowPolicy, Rate, TokenBucketPolicy};
+    use crate::storage::InMemoryStorage;
+    use crate::{Clock, Duration, MockClock};
+
+    #[test]
+    fn enforces_only_sampled_requests() {
+        let mut storage = InMemoryStorage::new();
+        let inner =
+            FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut storage).unwrap();
+        let mut never = SampledPolicy::new(inner, 0.).unwrap();
+
+        for _ in 0..3 {
+            assert!(never.consume(1).unwrap().rate_limit.is_accepted());
+        }
+
+        let mut always = SampledPolicy::new(never.into_inner(), 1.).unwrap();
+        assert!(!always.consume(1).unwrap().rate_limit.is_accepted());
+    }
+
+    #[test]
+    fn seeded_sampling_enforces_some_requests() {
+        let outcomes = |seed| {
+            let mut storage = InMemoryStorage::new();
+            let inner = FixedWindowPolicy::new(1, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap();
+            let mut policy = SampledPolicy::new(inner, 0.5)
+                .unwrap()
+                .with_random(XorShiftRandom::from_seed(seed));
+
+            (0..20)
+                .map(|_| policy.consume(1).unwrap().rate_limit.is_accepted())
+                .collect::<Vec<_>>()
+        };
+
+        let first = outcomes(7);
+        assert_eq!(first, outcomes(7));
+        assert!(first[1..].contains(&true));
+        assert!(first[1..].contains(&false));
+    }
+
+    #[test]
+    fn unsampled_requests_do_not_queue_reservations() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let inner = TokenBucketPolicy::new(2, "key".into(), Rate::per_minute(2), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+        let mut policy = SampledPolicy::new(inner, 0.)
+            .unwrap()
+            .with_random(XorShiftRandom::from_seed(7));
+
+        for _ in 0..10 {
+            let rate_limit = policy.consume(1).unwrap().rate_limit;
+            assert!(rate_limit.is_accepted());
+            assert_eq!(rate_limit.get_retry_after(), clock.now());
+        }
+        assert!(policy.consume(5).unwrap().rate_limit.is_accepted());
+
+        // The unsampled requests took the tokens but booked nothing ahead.
+        let mut policy = SampledPolicy::new(policy.into_inner(), 1.).unwrap();
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert!(rejected.get_retry_after() <= clock.now() + Duration::minutes(1));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::error::ReserveError;
+use crate::policy::Policy;
+use crate::{Clock, Duration, LocalDateTime, MockClock};
+
+/// How many of the requests sent by [`simulate()`] were accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Simulation {
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+/// Answers "if `requests` requests of `tokens` tokens were sent evenly over the
+/// next `over`, how many would be accepted", e.g. for client capacity planning.
+///
+/// The requests are consumed from `policy`, which must read the time from
+/// `clock`. To leave the real state untouched, build the policy over a copy of
+/// the key made with [`crate::storage::InMemoryStorage::fork()`], with the clock
+/// set to the current time. The clock is moved forward by `over`.
+pub fn simulate<P: Policy>(
+    policy: &mut P,
+    clock: &MockClock,
+    requests: u64,
+    tokens: u64,
+    over: Duration,
+) -> Result<Simulation, ReserveError> {
+    let mut simulation = Simulation {
+        accepted: 0,
+        rejected: 0,
+    };
+
+    if requests == 0 {
+        return Ok(simulation);
+    }
+
+    let step = over.num_milliseconds() / i64::try_from(requests).unwrap_or(i64::MAX);
+    let start = clock.now();
+
+    for request in 0..requests {
+        let offset = step.saturating_mul(i64::try_from(request).unwrap_or(i64::MAX));
+        clock.set(start + Duration::milliseconds(offset));
+
+        if policy.consume(tokens)?.rate_limit.is_accepted() {
+            simulation.accepted += 1;
+        } else {
+            simulation.rejected += 1;
+        }
+    }
+
+    clock.set(start + over);
+
+    Ok(simulation)
+}
+
+/// A decision taken in production, e.g. read back from an audit log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedDecision {
+    pub at: LocalDateTime,
+    pub tokens: u64,
+    pub accepted: bool,
+}
+
+/// How the decisions replayed by [`replay()`] compare with the recorded ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Replay {
+    pub unchanged: u64,
+    /// Recorded as rejected, accepted by the replayed policy.
+    pub newly_accepted: u64,
+    /// Recorded as accepted, rejected by the replayed policy.
+    pub newly_rejected: u64,
+}
+
+/// Feeds recorded traffic through another policy configuration and reports how
+/// the outcomes would differ, e.g. to pick a new limit from real traffic.
+///
+/// The decisions are consumed from `policy` in the given order, at their
+/// recorded time, so they should be sorted by time. The policy must read the
+/// time from `clock` and should be built over a scratch storage.
+pub fn replay<P: Policy>(
+    policy: &mut P,
+    clock: &MockClock,
+    decisions: &[RecordedDecision],
+) -> Result<Replay, ReserveError> {
+    let mut replay = Replay {
+        unchanged: 0,
+        newly_accepted: 0,
+        newly_rejected: 0,
+    };
+
+    for decision in decisions {
+        clock.set(decision.at);
+
+        match (
+            decision.accepted,
+            policy.consume(decision.tokens)?.rate_limit.is_accepted(),
+        ) {
+            (false, true) => replay.newly_accepted += 1,
+            (true, false) => replay.newly_rejected += 1,
+            _ => replay.unchanged += 1,
+        }
+    }
+
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FixedWindowPolicy;
+    use crate::storage::{InMemoryStorage, Storage};
+
+    #[test]
+    fn simulates_on_a_copy_of_the_key() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone())
+            .consume(4)
+            .unwrap();
+
+        let mut fork = InMemoryStorage::fork(&storage, "key");
+        let mut simulated =
+            FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut fork)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        // 6 tokens are left in the current window, the next one starts half way.
+        let simulation = simulate(&mut simulated, &clock, 20, 1, Duration::minutes(2)).unwrap();
+        assert_eq!(simulation.accepted, 16);
+        assert_eq!(simulation.rejected, 4);
+
+        assert_eq!(storage.fetch("key").unwrap().hit_count, 4);
+    }
+
+    #[test]
+    fn replays_recorded_decisions_against_a_new_limit() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let decisions: Vec<_> = (0..6)
+            .map(|second| RecordedDecision {
+                at: start + Duration::seconds(second),
+                tokens: 1,
+                accepted: second < 3,
+            })
+            .collect();
+
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        let replay = replay(&mut policy, &clock, &decisions).unwrap();
+        assert_eq!(
+            replay,
+            Replay {
+                unchanged: 4,
+                newly_accepted: 2,
+                newly_rejected: 0,
+            }
+        );
+    }
+}
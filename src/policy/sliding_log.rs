@@ -0,0 +1,389 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::Policy;
+use crate::storage::{State, Storage};
+use crate::{
+    ChronoTimestampMillis, Clock, Duration, LocalDateTime, LocalTime, RateLimit, Reservation,
+    SystemClock, Unit,
+};
+use chrono::TimeZone;
+use std::collections::VecDeque;
+
+/// Counts the exact number of tokens taken within the rolling interval.
+///
+/// Unlike [`crate::policy::SlidingWindowPolicy`], which weights the previous
+/// window, this policy keeps a log of every hit of the key and drops entries once
+/// they are older than the interval. The log is bounded by the limit: it holds at
+/// most `limit` entries, reservations waiting for room included, and a waiting
+/// reservation that would need an entry in a full log is rejected as if it could
+/// not wait that long. Memory grows with the limit, not with the traffic.
+pub struct SlidingLogPolicy<'a, Store: Storage<SlidingLogState, SlidingLogState>> {
+    limit: u64,
+    key: String,
+    interval: Duration,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl<Store: Storage<SlidingLogState, SlidingLogState>> Policy for SlidingLogPolicy<'_, Store> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens > self.limit {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.limit,
+            });
+        }
+
+        let now = self.clock.now();
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| SlidingLogState::new(self.key.clone(), &self.interval));
+
+        state.evict(&now);
+        let available_tokens = self.limit.saturating_sub(state.get_hit_count());
+
+        let reservation = if tokens == 0 {
+            let retry_after = if available_tokens > 0 {
+                now
+            } else {
+                LocalTime::timestamp_millis_opt(
+                    &LocalTime,
+                    now.timestamp_millis() + state.calculate_time_for_tokens(self.limit, 1, &now),
+                )
+                .unwrap()
+            };
+
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens,
+                    retry_after,
+                    accepted: true,
+                    limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else if available_tokens >= tokens {
+            state.add(tokens, &now, &now);
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens: self.limit.saturating_sub(state.get_hit_count()),
+                    retry_after: now,
+                    accepted: true,
+                    limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else {
+            let wait_duration = state.calculate_time_for_tokens(self.limit, tokens, &now);
+            let retry_after =
+                LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + wait_duration)
+                    .unwrap();
+
+            if max_time.is_some_and(|max_time| wait_duration > max_time)
+                || state.is_full(self.limit)
+            {
+                return Err(ReserveError::MaxWaitDurationExceededError {
+                    rate_limit: RateLimit {
+                        available_tokens,
+                        retry_after,
+                        accepted: false,
+                        limit: self.limit,
+                        reset_at: state.get_reset_time(&now),
+                        unit: self.unit.clone(),
+                    },
+                });
+            }
+
+            // Logged at the moment the reservation acts, so it counts against
+            // the interval that starts then.
+            state.add(tokens, &retry_after, &now);
+
+            Reservation {
+                time_to_act: retry_after,
+                rate_limit: RateLimit {
+                    available_tokens: 0,
+                    retry_after,
+                    accepted: false,
+                    limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        };
+
+        if tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.evict(&self.clock.now());
+            state.refund(tokens);
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<SlidingLogState, SlidingLogState>> SlidingLogPolicy<'a, Store> {
+    pub fn new(
+        limit: u64,
+        key: String,
+        interval: Duration,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        Ok(Self {
+            limit,
+            key,
+            interval,
+            storage,
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SlidingLogState {
+    pub key: String,
+    pub interval: ChronoTimestampMillis,
+    /// Hit times with the number of tokens taken, ordered by time, at most as many
+    /// entries as the limit. Reservations waiting for room are logged at their
+    /// future time to act.
+    pub log: VecDeque<(ChronoTimestampMillis, u64)>,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+}
+
+impl State<SlidingLogState> for SlidingLogState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.interval).unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
+    }
+}
+
+impl SlidingLogState {
+    pub fn new(key: String, interval: &Duration) -> Self {
+        Self {
+            key,
+            interval: interval.num_milliseconds(),
+            log: VecDeque::new(),
+            created_at: None,
+            last_hit_at: None,
+        }
+    }
+
+    /// Drops the entries that are out of the interval ending now.
+    pub fn evict(&mut self, now: &LocalDateTime) {
+        let now = now.timestamp_millis();
+
+        while let Some((hit_at, _)) = self.log.front() {
+            if hit_at + self.interval > now {
+                break;
+            }
+
+            self.log.pop_front();
+        }
+    }
+
+    /// Logs `tokens` taken at `at`, hit by a request made `now`.
+    pub fn add(&mut self, tokens: u64, at: &LocalDateTime, now: &LocalDateTime) {
+        let at = at.timestamp_millis();
+        let position = self.log.partition_point(|(hit_at, _)| *hit_at <= at);
+
+        match self.log.get_mut(position.wrapping_sub(1)) {
+            Some((hit_at, hits)) if *hit_at == at => *hits = hits.saturating_add(tokens),
+            _ => self.log.insert(position, (at, tokens)),
+        }
+
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+    }
+
+    /// Removes `tokens` from the log, latest entries first. Expects an evicted state.
+    pub fn refund(&mut self, mut tokens: u64) {
+        while tokens > 0 {
+            let Some((_, hits)) = self.log.back_mut() else {
+                break;
+            };
+
+            if *hits > tokens {
+                *hits -= tokens;
+                break;
+            }
+
+            tokens -= *hits;
+            self.log.pop_back();
+        }
+    }
+
+    /// Returns true when the log holds `max_size` entries and has no room for another.
+    pub fn is_full(&self, max_size: u64) -> bool {
+        u64::try_from(self.log.len()).unwrap_or(u64::MAX) >= max_size
+    }
+
+    /// Returns the tokens logged within the interval, including pending reservations.
+    /// Expects an evicted state.
+    pub fn get_hit_count(&self) -> u64 {
+        self.log
+            .iter()
+            .fold(0, |count, (_, hits)| count.saturating_add(*hits))
+    }
+
+    /// Returns the milliseconds until `tokens` tokens fit within `max_size`. Expects an evicted state.
+    pub fn calculate_time_for_tokens(
+        &self,
+        max_size: u64,
+        tokens: u64,
+        now: &LocalDateTime,
+    ) -> i64 {
+        let mut excess = self
+            .get_hit_count()
+            .saturating_sub(max_size.saturating_sub(tokens));
+
+        if excess == 0 {
+            return 0;
+        }
+
+        for (hit_at, hits) in &self.log {
+            excess = excess.saturating_sub(*hits);
+
+            if excess == 0 {
+                return (hit_at + self.interval - now.timestamp_millis()).max(0);
+            }
+        }
+
+        0
+    }
+
+    /// Returns the moment every logged entry has left the interval.
+    pub fn get_reset_time(&self, now: &LocalDateTime) -> LocalDateTime {
+        match self.log.back() {
+            Some((hit_at, _)) => {
+                LocalTime::timestamp_millis_opt(&LocalTime, hit_at + self.interval).unwrap()
+            }
+            None => *now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::conformance;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn sliding_log_policy_conforms() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = SlidingLogPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+
+        conformance::run(
+            &mut policy,
+            &clock,
+            5,
+            Duration::minutes(1),
+            5,
+            Duration::minutes(1),
+        );
+    }
+
+    #[test]
+    fn counts_exactly_within_the_rolling_interval() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            SlidingLogPolicy::new(3, "key".into(), Duration::seconds(10), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(2).unwrap();
+        clock.advance(Duration::seconds(6));
+        policy.consume(1).unwrap();
+
+        let rejected = policy.consume(1).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(rejected.get_retry_after(), start + Duration::seconds(10));
+
+        clock.advance(Duration::seconds(4));
+        let rate_limit = policy.consume(2).unwrap().rate_limit;
+        assert!(rate_limit.is_accepted());
+        assert_eq!(rate_limit.get_remaining_tokens(), 0);
+    }
+
+    #[test]
+    fn waiting_reservations_are_bounded_by_the_limit() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            SlidingLogPolicy::new(3, "key".into(), Duration::seconds(10), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(1).unwrap();
+        clock.advance(Duration::seconds(1));
+        policy.consume(1).unwrap();
+        assert!(!policy.reserve(2, None).unwrap().rate_limit.is_accepted());
+
+        for _ in 0..10 {
+            assert!(policy.reserve(1, None).is_err());
+        }
+
+        drop(policy);
+        assert_eq!(storage.fetch("key").unwrap().log.len(), 3);
+    }
+}
@@ -1,27 +1,32 @@
 use crate::error::{PolicyError, ReserveError};
-use crate::policy::Policy;
+use crate::policy::{rescale_hits, CapHook, Policy, HIT_COUNT_OVERFLOW_FACTOR};
 use crate::storage::{State, Storage};
-use crate::LocalTime;
-use crate::{ChronoTimestampMillis, Duration, RateLimit, Reservation};
+use crate::{
+    ChronoTimestampMillis, Clock, Duration, RateLimit, Reservation, StateSnapshot, SystemClock,
+    Unit,
+};
+use crate::{LocalDateTime, LocalTime};
 use chrono::TimeZone;
-use std::cmp::{max, min};
-use std::ops::Add;
+use std::cmp::max;
 
 pub struct SlidingWindowPolicy<'a, Store: Storage<SlidingWindowState, SlidingWindowState>> {
-    limit: usize,
+    limit: u64,
     key: String,
     interval: chrono::Duration,
     storage: &'a mut Store,
+    unit: Unit,
+    min_spacing: Option<Duration>,
+    strict: bool,
+    on_cap: Option<CapHook>,
+    clock: Box<dyn Clock>,
 }
 
 impl<Store: Storage<SlidingWindowState, SlidingWindowState>> Policy
     for SlidingWindowPolicy<'_, Store>
 {
-    fn reserve(
-        &mut self,
-        tokens: usize,
-        max_time: Option<i64>,
-    ) -> Result<Reservation, ReserveError> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        self.check_arguments(None, max_time)?;
+
         if tokens > self.limit {
             // Cannot reserve more tokens than the size of the rate limiter.
             return Err(ReserveError::TooManyTokensError {
@@ -30,75 +35,92 @@ impl<Store: Storage<SlidingWindowState, SlidingWindowState>> Policy
             });
         }
 
-        let mut state = self
-            .storage
-            .fetch(self.key.as_str())
-            .unwrap_or_else(|| SlidingWindowState::new(self.key.clone(), &self.interval));
+        let now = self.clock.now();
+        let mut state = self.load_state(&now);
 
-        if state.is_expired() {
-            state = SlidingWindowState::create_from_previous_window(&state, &self.interval);
-        }
-
-        let now = LocalTime::now();
-        let hit_count = state.get_hit_count();
+        let hit_count = state.get_hit_count(&now);
         let available_tokens = self.get_available_tokens(hit_count);
+        let spacing_wait = self
+            .min_spacing
+            .map_or(0, |spacing| state.get_spacing_wait(&spacing, &now));
 
         let reservation = if tokens == 0 {
             let available_tokens = available_tokens.unwrap_or(0);
-            let reset_duration = state.calculate_time_for_tokens(self.limit, state.get_hit_count());
+            let reset_duration = state.calculate_time_for_tokens(self.limit, 1, &now);
             let reset_time = if available_tokens > 0 {
-                LocalTime::now()
+                now
             } else {
                 LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + reset_duration)
                     .unwrap()
             };
 
             Reservation {
-                time_to_act: now.clone(),
+                time_to_act: now,
                 rate_limit: RateLimit {
                     available_tokens,
                     retry_after: reset_time,
                     accepted: true,
                     limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
                 },
             }
-        } else if available_tokens.is_some() && available_tokens.unwrap() >= tokens {
-            state.add(Some(tokens));
+        } else if spacing_wait == 0
+            && available_tokens.is_some()
+            && available_tokens.unwrap() >= tokens
+        {
+            self.report_cap(state.add(Some(tokens), self.limit, &now));
+            state.last_accepted_at = Some(now.timestamp_millis());
             Reservation {
-                time_to_act: now.clone(),
+                time_to_act: now,
                 rate_limit: RateLimit {
                     available_tokens: self
-                        .get_available_tokens(state.get_hit_count())
+                        .get_available_tokens(state.get_hit_count(&now))
                         .unwrap_or(0),
-                    retry_after: now.clone(),
+                    retry_after: now,
                     accepted: true,
                     limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
                 },
             }
         } else {
-            let wait_duration = state.calculate_time_for_tokens(self.limit, tokens);
+            let wait_duration = state
+                .calculate_time_for_tokens(self.limit, tokens, &now)
+                .max(spacing_wait);
+            let retry_after =
+                LocalTime::timestamp_millis_opt(&LocalTime, wait_duration + now.timestamp_millis())
+                    .unwrap();
 
             if let Some(max_time) = max_time {
                 if wait_duration > max_time {
-                    return Err(ReserveError::MaxWaitDurationExceededError);
+                    return Err(ReserveError::MaxWaitDurationExceededError {
+                        rate_limit: RateLimit {
+                            available_tokens: available_tokens.unwrap_or(0),
+                            retry_after,
+                            accepted: false,
+                            limit: self.limit,
+                            reset_at: state.get_reset_time(&now),
+                            unit: self.unit.clone(),
+                        },
+                    });
                 }
             }
 
-            state.add(Some(tokens));
-
-            let retry_after =
-                LocalTime::timestamp_millis_opt(&LocalTime, wait_duration + now.timestamp_millis())
-                    .unwrap();
+            self.report_cap(state.add(Some(tokens), self.limit, &now));
+            state.last_accepted_at = Some(retry_after.timestamp_millis());
 
             Reservation {
-                time_to_act: retry_after.clone(),
+                time_to_act: retry_after,
                 rate_limit: RateLimit {
                     available_tokens: self
-                        .get_available_tokens(state.get_hit_count())
+                        .get_available_tokens(state.get_hit_count(&now))
                         .unwrap_or(0),
                     retry_after,
                     accepted: false,
                     limit: self.limit,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
                 },
             }
         };
@@ -110,14 +132,55 @@ impl<Store: Storage<SlidingWindowState, SlidingWindowState>> Policy
         Ok(reservation)
     }
 
-    fn consume(&mut self, tokens: usize) -> Result<Reservation, ReserveError> {
-        self.reserve(tokens, None)
+    fn refund(&mut self, tokens: u64) {
+        let now = self.clock.now();
+
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            if state.is_expired(&now) {
+                state =
+                    SlidingWindowState::create_from_previous_window(&state, &self.interval, &now);
+            }
+
+            state.refund(tokens);
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn penalize(&mut self, tokens: u64) -> Result<(), ReserveError> {
+        self.check_arguments(Some(tokens), None)?;
+
+        if tokens == 0 {
+            return Ok(());
+        }
+
+        let now = self.clock.now();
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| SlidingWindowState::new(self.key.clone(), &self.interval, &now));
+
+        if state.is_expired(&now) {
+            state = SlidingWindowState::create_from_previous_window(&state, &self.interval, &now);
+        }
+
+        self.report_cap(state.add(Some(tokens), self.limit, &now));
+        self.storage.save(&self.key, state);
+
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
     }
 }
 
 impl<'a, Store: Storage<SlidingWindowState, SlidingWindowState>> SlidingWindowPolicy<'a, Store> {
     pub fn new(
-        limit: usize,
+        limit: u64,
         key: String,
         interval: Duration,
         storage: &'a mut Store,
@@ -135,10 +198,268 @@ impl<'a, Store: Storage<SlidingWindowState, SlidingWindowState>> SlidingWindowPo
             key,
             interval,
             storage,
+            unit: Unit::default(),
+            min_spacing: None,
+            strict: false,
+            on_cap: None,
+            clock: Box::new(SystemClock),
         })
     }
 
-    fn get_available_tokens(&self, hit_count: usize) -> Option<usize> {
+    /// Same as [`Self::new()`], in strict mode: a non-positive interval fails here,
+    /// and zero token penalties and late events as well as negative waits and
+    /// spacings fail with a [`PolicyError::InvalidArgumentError`] instead of being
+    /// silently accepted.
+    pub fn new_strict(
+        limit: u64,
+        key: String,
+        interval: Duration,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if interval <= Duration::zero() {
+            return Err(PolicyError::InvalidArgumentError(
+                "the interval must be positive",
+            ));
+        }
+
+        let mut policy = Self::new(limit, key, interval, storage)?;
+        policy.strict = true;
+
+        Ok(policy)
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Calls `on_cap` with the key whenever its hit count is capped at
+    /// [`HIT_COUNT_OVERFLOW_FACTOR`] times the limit.
+    pub fn with_cap_hook<F: FnMut(&str) + Send + 'static>(mut self, on_cap: F) -> Self {
+        self.on_cap = Some(Box::new(on_cap));
+        self
+    }
+
+    /// Keeps accepted requests at least `min_spacing` apart, even when tokens remain.
+    pub fn with_min_spacing(mut self, min_spacing: Duration) -> Self {
+        self.min_spacing = Some(min_spacing);
+        self
+    }
+
+    /// Consumes `tokens` for an event that happened `at`, charging them to the
+    /// window the event falls in, e.g. for events processed with a delay.
+    ///
+    /// Events of the current window are checked like [`Policy::consume()`] at
+    /// their own time, events of the previous window against the hits of that
+    /// window. Lateness is bounded by the previous window: older events fail
+    /// with [`ReserveError::TooLateError`]. Events in the future count as now.
+    pub fn consume_at(
+        &mut self,
+        tokens: u64,
+        at: LocalDateTime,
+    ) -> Result<Reservation, ReserveError> {
+        self.check_arguments(Some(tokens), None)?;
+
+        if tokens > self.limit {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.limit,
+            });
+        }
+
+        let now = self.clock.now();
+        let at = at.min(now);
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| SlidingWindowState::new(self.key.clone(), &self.interval, &now));
+
+        if state.is_expired(&now) {
+            state = SlidingWindowState::create_from_previous_window(&state, &self.interval, &now);
+        }
+
+        let window_start_at = state.window_end_at - state.interval;
+        let in_current_window = at.timestamp_millis() >= window_start_at;
+
+        if !in_current_window && at.timestamp_millis() < window_start_at - state.interval {
+            return Err(ReserveError::TooLateError);
+        }
+
+        let available_tokens = if in_current_window {
+            self.get_available_tokens(state.get_hit_count(&at))
+        } else {
+            self.get_available_tokens(state.hit_count_for_last_window)
+        };
+        let accepted = available_tokens.is_some_and(|available_tokens| available_tokens >= tokens);
+
+        if accepted && tokens > 0 {
+            if in_current_window {
+                self.report_cap(state.add(Some(tokens), self.limit, &now));
+            } else {
+                self.report_cap(state.add_to_previous_window(tokens, self.limit, &now));
+            }
+        }
+
+        let retry_after = if accepted {
+            now
+        } else {
+            LocalTime::timestamp_millis_opt(
+                &LocalTime,
+                now.timestamp_millis() + state.calculate_time_for_tokens(self.limit, tokens, &now),
+            )
+            .unwrap()
+        };
+        let reservation = Reservation {
+            time_to_act: retry_after,
+            rate_limit: RateLimit {
+                available_tokens: self
+                    .get_available_tokens(state.get_hit_count(&now))
+                    .unwrap_or(0),
+                retry_after,
+                accepted,
+                limit: self.limit,
+                reset_at: state.get_reset_time(&now),
+                unit: self.unit.clone(),
+            },
+        };
+
+        if accepted && tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    /// Changes the limit of the key, keeping the current windows.
+    ///
+    /// The hits of the current and the previous window are rescaled so the same
+    /// share of the limit stays used, e.g. 5 hits out of 10 become 10 hits out of 20.
+    pub fn set_limit(&mut self, limit: u64) -> Result<(), PolicyError> {
+        if limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.rescale(self.limit, limit);
+            self.storage.save(&self.key, state);
+        }
+
+        self.limit = limit;
+
+        Ok(())
+    }
+
+    /// Changes the interval of the key. The current window keeps its start and
+    /// its hits, and ends `interval` after its start.
+    pub fn set_interval(&mut self, interval: Duration) {
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.set_interval(&interval);
+            self.storage.save(&self.key, state);
+        }
+
+        self.interval = interval;
+    }
+
+    /// Returns how long until `tokens` tokens are available, without recording anything.
+    pub fn time_until_available(&self, tokens: u64) -> Result<Duration, ReserveError> {
+        if tokens > self.limit {
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.limit,
+            });
+        }
+
+        let now = self.clock.now();
+        let state = self.load_state(&now);
+        let mut wait = state
+            .calculate_time_for_tokens(self.limit, tokens, &now)
+            .max(0);
+
+        if let (Some(spacing), true) = (self.min_spacing, tokens > 0) {
+            wait = wait.max(state.get_spacing_wait(&spacing, &now));
+        }
+
+        Ok(Duration::milliseconds(wait))
+    }
+
+    /// Validates the arguments of a call in strict mode, `hits` being the hits it adds as is.
+    fn check_arguments(&self, hits: Option<u64>, max_time: Option<i64>) -> Result<(), PolicyError> {
+        if !self.strict {
+            return Ok(());
+        }
+
+        if hits == Some(0) {
+            return Err(PolicyError::InvalidArgumentError(
+                "at least one hit must be added",
+            ));
+        }
+
+        if max_time.is_some_and(|max_time| max_time < 0) {
+            return Err(PolicyError::InvalidArgumentError(
+                "the maximum wait must not be negative",
+            ));
+        }
+
+        if self
+            .min_spacing
+            .is_some_and(|spacing| spacing < Duration::zero())
+        {
+            return Err(PolicyError::InvalidArgumentError(
+                "the minimum spacing must not be negative",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the state of the key and slides its window up to `now`.
+    fn load_state(&self, now: &LocalDateTime) -> SlidingWindowState {
+        let state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| SlidingWindowState::new(self.key.clone(), &self.interval, now));
+
+        if state.is_expired(now) {
+            return SlidingWindowState::create_from_previous_window(&state, &self.interval, now);
+        }
+
+        state
+    }
+
+    /// Returns what the sliding window of the key counts, or None when the key has
+    /// no state. The hit count includes the weighted share of the previous window.
+    pub fn state(&self) -> Option<StateSnapshot> {
+        let mut state = self.storage.fetch(self.key.as_str())?;
+        let now = self.clock.now();
+
+        if state.is_expired(&now) {
+            state = SlidingWindowState::create_from_previous_window(&state, &self.interval, &now);
+        }
+
+        Some(StateSnapshot {
+            hit_count: state.get_hit_count(&now),
+            window_end: LocalTime::timestamp_millis_opt(&LocalTime, state.window_end_at).unwrap(),
+            limit: self.limit,
+            interval: Duration::milliseconds(state.interval),
+        })
+    }
+
+    /// Tells the cap hook, if any, that the hit count of the key was capped.
+    fn report_cap(&mut self, capped: bool) {
+        if let (true, Some(on_cap)) = (capped, &mut self.on_cap) {
+            on_cap(&self.key);
+        }
+    }
+
+    fn get_available_tokens(&self, hit_count: u64) -> Option<u64> {
         if hit_count > self.limit {
             return None; // Avoid to subtract with overflow
         }
@@ -147,13 +468,17 @@ impl<'a, Store: Storage<SlidingWindowState, SlidingWindowState>> SlidingWindowPo
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SlidingWindowState {
     pub key: String,
-    hit_count: usize,
-    hit_count_for_last_window: usize,
+    hit_count: u64,
+    hit_count_for_last_window: u64,
     pub interval: ChronoTimestampMillis,
     pub window_end_at: ChronoTimestampMillis,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+    /// When the last accepted or reserved request acts, used by the minimum spacing.
+    pub last_accepted_at: Option<ChronoTimestampMillis>,
 }
 
 impl State<SlidingWindowState> for SlidingWindowState {
@@ -161,27 +486,47 @@ impl State<SlidingWindowState> for SlidingWindowState {
         self.key.clone()
     }
 
-    fn get_expiration_time(&self) -> usize {
-        self.interval as usize
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.interval).unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
     }
 }
 
 impl SlidingWindowState {
-    pub fn new(key: String, interval: &chrono::Duration) -> Self {
+    pub fn new(key: String, interval: &chrono::Duration, now: &LocalDateTime) -> Self {
         Self {
             key,
             hit_count: 0,
             hit_count_for_last_window: 0,
             interval: interval.num_milliseconds(),
-            window_end_at: LocalTime::now().timestamp_millis() + interval.num_milliseconds(),
+            window_end_at: now.timestamp_millis() + interval.num_milliseconds(),
+            created_at: None,
+            last_hit_at: None,
+            last_accepted_at: None,
         }
     }
 
-    pub fn create_from_previous_window(window: &Self, interval: &chrono::Duration) -> Self {
-        let mut new = Self::new(window.key.clone(), interval);
+    pub fn create_from_previous_window(
+        window: &Self,
+        interval: &chrono::Duration,
+        now: &LocalDateTime,
+    ) -> Self {
+        let mut new = Self::new(window.key.clone(), interval, now);
+        new.created_at = window.created_at;
+        new.last_hit_at = window.last_hit_at;
+        new.last_accepted_at = window.last_accepted_at;
         let window_end_at = window.window_end_at + interval.num_milliseconds();
 
-        if LocalTime::now().timestamp_millis() < window_end_at {
+        if now.timestamp_millis() < window_end_at {
             new.hit_count_for_last_window = window.hit_count;
             new.window_end_at = window_end_at;
         }
@@ -189,70 +534,254 @@ impl SlidingWindowState {
         new
     }
 
-    pub fn get_expiration_time(&self) -> ChronoTimestampMillis {
+    /// Returns the hits recorded in the current window.
+    pub fn get_current_window_hits(&self) -> u64 {
+        self.hit_count
+    }
+
+    /// Returns the hits recorded in the previous window.
+    pub fn get_previous_window_hits(&self) -> u64 {
+        self.hit_count_for_last_window
+    }
+
+    pub fn get_expiration_time(&self, now: &LocalDateTime) -> ChronoTimestampMillis {
         // TODO : Maybe subtract with overflow?
-        self.window_end_at + self.interval - LocalTime::now().timestamp_millis()
+        self.window_end_at + self.interval - now.timestamp_millis()
+    }
+
+    pub fn is_expired(&self, now: &LocalDateTime) -> bool {
+        now.timestamp_millis() >= self.window_end_at
+    }
+
+    /// Returns the moment all hits recorded so far have slid out of the window.
+    pub fn get_reset_time(&self, now: &LocalDateTime) -> LocalDateTime {
+        let reset_at = if self.hit_count > 0 {
+            self.window_end_at + self.interval
+        } else if self.hit_count_for_last_window > 0 {
+            self.window_end_at
+        } else {
+            return *now;
+        };
+
+        LocalTime::timestamp_millis_opt(&LocalTime, reset_at).unwrap()
+    }
+
+    /// Adds `hits` to the current window, capping the hit count at
+    /// [`HIT_COUNT_OVERFLOW_FACTOR`] times `max_size`. Returns true when the cap was hit.
+    pub fn add(&mut self, hits: Option<u64>, max_size: u64, now: &LocalDateTime) -> bool {
+        // Strict policies reject zero hits before they get here.
+        let hits = hits.unwrap_or(1);
+        let max_hit_count = max_size.saturating_mul(HIT_COUNT_OVERFLOW_FACTOR);
+        let hit_count = self.hit_count.saturating_add(hits);
+        self.hit_count = hit_count.min(max_hit_count);
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+
+        hit_count > max_hit_count
+    }
+
+    /// Gives `tokens` back, from the current window first and then from the previous one.
+    pub fn refund(&mut self, tokens: u64) {
+        let from_current = tokens.min(self.hit_count);
+
+        self.hit_count -= from_current;
+        self.hit_count_for_last_window = self
+            .hit_count_for_last_window
+            .saturating_sub(tokens - from_current);
+    }
+
+    /// Adds `hits` to the previous window, capping its hit count like [`Self::add()`].
+    /// Returns true when the cap was hit.
+    pub fn add_to_previous_window(
+        &mut self,
+        hits: u64,
+        max_size: u64,
+        now: &LocalDateTime,
+    ) -> bool {
+        let max_hit_count = max_size.saturating_mul(HIT_COUNT_OVERFLOW_FACTOR);
+        let hit_count = self.hit_count_for_last_window.saturating_add(hits);
+        self.hit_count_for_last_window = hit_count.min(max_hit_count);
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+
+        hit_count > max_hit_count
+    }
+
+    /// Rescales the hits counted against a limit of `from` to a limit of `to`.
+    pub fn rescale(&mut self, from: u64, to: u64) {
+        self.hit_count = rescale_hits(self.hit_count, from, to);
+        self.hit_count_for_last_window = rescale_hits(self.hit_count_for_last_window, from, to);
     }
 
-    pub fn is_expired(&self) -> bool {
-        LocalTime::now().timestamp_millis() > self.window_end_at
+    /// Moves the end of the current window to `interval` after its start.
+    pub fn set_interval(&mut self, interval: &chrono::Duration) {
+        let window_start_at = self.window_end_at - self.interval;
+
+        self.interval = interval.num_milliseconds();
+        self.window_end_at = window_start_at + self.interval;
     }
 
-    pub fn add(&mut self, hits: Option<usize>) {
-        let hits = hits.unwrap_or(1); // TODO : maybe error if hits == 0?
-        self.hit_count += hits;
+    /// Returns the milliseconds until a request is `spacing` away from the last accepted one.
+    pub fn get_spacing_wait(&self, spacing: &Duration, now: &LocalDateTime) -> i64 {
+        self.last_accepted_at.map_or(0, |last_accepted_at| {
+            (last_accepted_at + spacing.num_milliseconds() - now.timestamp_millis()).max(0)
+        })
     }
 
     /// Calculates the sliding window number of request.
-    pub fn get_hit_count(&self) -> usize {
+    pub fn get_hit_count(&self, now: &LocalDateTime) -> u64 {
         let start_of_window = self.window_end_at - self.interval;
-        let percent_of_current_time_frame =
-            min(LocalTime::now().timestamp_millis() - start_of_window, 1) as usize;
+        let percent_of_current_time_frame = ((now.timestamp_millis() - start_of_window) as f64
+            / self.interval as f64)
+            .clamp(0., 1.);
 
-        // TODO : Maybe subtract with overflow?
-        self.hit_count_for_last_window * (1 - percent_of_current_time_frame) + self.hit_count
-    }
+        let last_window_hits =
+            (self.hit_count_for_last_window as f64 * (1. - percent_of_current_time_frame)).floor();
 
-    pub fn calculate_time_for_tokens(&self, max_size: usize, tokens: usize) -> i64 {
-        let remaining = max_size - self.get_hit_count();
+        (last_window_hits as u64).saturating_add(self.hit_count)
+    }
 
-        if remaining >= tokens {
+    pub fn calculate_time_for_tokens(
+        &self,
+        max_size: u64,
+        tokens: u64,
+        now: &LocalDateTime,
+    ) -> i64 {
+        if max_size.saturating_sub(self.get_hit_count(now)) >= tokens {
             return 0;
         }
 
-        let time = LocalTime::now().timestamp_millis();
         let start_of_window = self.window_end_at - self.interval;
-        let time_passed = time - start_of_window;
-
-        // https://github.com/symfony/rate-limiter/blob/f1fbc60e7fed63f1c77bbf8601170cc80fddd95a/Policy/SlidingWindow.php#L97
-        let window_passed: f64 = {
-            // I would do it via std::cmp::min, but Ord<f64> is not implemented,
-            // so you can't do without that shit
-            let value = time_passed as f64 / self.interval as f64;
-            if value > 1. {
-                1.
-            } else {
-                value
+        let allowed = max_size
+            .checked_sub(self.hit_count)
+            .and_then(|remaining| remaining.checked_sub(tokens));
+
+        let available_at = match allowed {
+            // Hits of the current window stay until it ends,
+            // so only the decaying previous window can make room.
+            Some(allowed) => {
+                start_of_window
+                    + Self::time_to_decay(self.hit_count_for_last_window, allowed, self.interval)
+            }
+            // Otherwise wait for the current window to become the previous one.
+            None => {
+                self.window_end_at
+                    + Self::time_to_decay(
+                        self.hit_count,
+                        max_size.saturating_sub(tokens),
+                        self.interval,
+                    )
             }
         };
 
-        // https://github.com/symfony/rate-limiter/blob/f1fbc60e7fed63f1c77bbf8601170cc80fddd95a/Policy/SlidingWindow.php#L98
-        let releasable = max(
-            1,
-            max_size
-                - ((self.hit_count_for_last_window as f64 * (1. - window_passed)).floor() as usize),
-        );
-
-        let remaining_window = (self.interval - time_passed) as usize;
-        let needed = tokens - remaining;
+        max(0, available_at - now.timestamp_millis())
+    }
 
-        if releasable >= needed {
-            return (needed as f64 * (remaining_window as f64 / max(1, releasable) as f64)) as i64;
+    /// Returns how far into a window the weighted previous window hits,
+    /// `floor(hits * (1 - elapsed / interval))`, drop to `allowed` or less.
+    fn time_to_decay(hits: u64, allowed: u64, interval: ChronoTimestampMillis) -> i64 {
+        if hits <= allowed {
+            return 0;
         }
 
-        // TODO : Refactor
+        let fraction = 1. - (allowed + 1) as f64 / hits as f64;
+
+        (interval as f64 * fraction).floor() as i64 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn late_events_count_against_their_window() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            SlidingWindowPolicy::new(4, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(1).unwrap();
+        clock.advance(Duration::seconds(90));
+
+        // The previous window already counted one hit.
+        let late = start + Duration::seconds(10);
+        assert!(policy.consume_at(3, late).unwrap().rate_limit.is_accepted());
+        assert!(!policy.consume_at(1, late).unwrap().rate_limit.is_accepted());
+        assert!(policy
+            .consume_at(2, clock.now())
+            .unwrap()
+            .rate_limit
+            .is_accepted());
+
+        assert!(matches!(
+            policy.consume_at(1, start - Duration::seconds(1)),
+            Err(ReserveError::TooLateError)
+        ));
+    }
+
+    #[test]
+    fn previous_window_hits_decay_with_elapsed_time() {
+        let now = LocalTime::now();
+        let interval = Duration::seconds(100);
+        let mut state = SlidingWindowState::new("key".into(), &interval, &now);
+        state.hit_count_for_last_window = 10;
+        state.hit_count = 2;
+        // A quarter of the current window has passed.
+        state.window_end_at = now.timestamp_millis() + 75_000;
+
+        // floor(10 * 0.75) previous hits still count, plus the current ones.
+        assert_eq!(state.get_hit_count(&now), 9);
+    }
+
+    #[test]
+    fn retry_after_is_the_first_moment_enough_hits_have_decayed() {
+        let clock = MockClock::default();
+        let start = clock.now();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            SlidingWindowPolicy::new(10, "key".into(), Duration::seconds(100), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        policy.consume(10).unwrap();
+        clock.set(start + Duration::seconds(100));
+
+        // floor(10 * (1 - elapsed / 100s)) drops to 5 just after 40 seconds.
+        let retry_after = policy.consume(5).unwrap().rate_limit.get_retry_after();
+        assert_eq!(retry_after, start + Duration::milliseconds(140_001));
+
+        clock.set(retry_after - Duration::milliseconds(1));
+        assert!(!policy.consume(5).unwrap().rate_limit.is_accepted());
+
+        clock.set(retry_after);
+        assert!(policy.consume(5).unwrap().rate_limit.is_accepted());
+    }
 
-        (self.window_end_at - time)
-            + (needed as i64 - releasable as i64) * (self.interval / max_size as i64)
+    #[test]
+    fn cap_hook_fires_when_the_hit_count_is_capped() {
+        let capped = Arc::new(AtomicUsize::new(0));
+        let counter = capped.clone();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            SlidingWindowPolicy::new(2, "key".into(), Duration::minutes(1), &mut storage)
+                .unwrap()
+                .with_cap_hook(move |key| {
+                    assert_eq!(key, "key");
+                    counter.fetch_add(1, Ordering::Relaxed);
+                });
+
+        policy.penalize(8).unwrap();
+        assert_eq!(capped.load(Ordering::Relaxed), 0);
+
+        policy.penalize(1).unwrap();
+        assert_eq!(capped.load(Ordering::Relaxed), 1);
     }
 }
@@ -0,0 +1,171 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::Policy;
+use crate::{Duration, LocalDateTime, RateLimit, Reservation};
+
+/// A share of the limit a [`ThresholdPolicy`] saw being used up.
+#[derive(Debug, Clone)]
+pub struct ThresholdCrossing {
+    /// The crossed share of the limit, between `0.0` and `1.0`.
+    pub threshold: f64,
+    /// The rate limit of the reservation that crossed it.
+    pub rate_limit: RateLimit,
+}
+
+/// Calls `on_crossing` when the share of the limit used by the key reaches one of
+/// the thresholds, e.g. to send "you are approaching your quota" emails at 50%,
+/// 80% and 100%.
+///
+/// A threshold fires when an accepted reservation takes the usage from below it
+/// to at least it, e.g. once per fixed window. With [`Self::with_debounce()`], a
+/// threshold does not fire again until the debounce duration has passed, so a
+/// key hovering around it in a sliding window does not fire it on every request.
+/// The callback can forward the crossings to a channel.
+pub struct ThresholdPolicy<P: Policy, F: FnMut(&ThresholdCrossing)> {
+    inner: P,
+    thresholds: Vec<f64>,
+    last_fired_at: Vec<Option<LocalDateTime>>,
+    debounce: Duration,
+    on_crossing: F,
+}
+
+impl<P: Policy, F: FnMut(&ThresholdCrossing)> Policy for ThresholdPolicy<P, F> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        let reservation = self.inner.reserve(tokens, max_time)?;
+
+        if tokens > 0 && reservation.rate_limit.accepted {
+            self.observe(tokens, &reservation.rate_limit);
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        self.inner.refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.last_fired_at.fill(None);
+        self.inner.reset();
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.inner.now()
+    }
+}
+
+impl<P: Policy, F: FnMut(&ThresholdCrossing)> ThresholdPolicy<P, F> {
+    /// Every threshold is a share of the limit, between `0.0` and `1.0`.
+    pub fn new(inner: P, thresholds: &[f64], on_crossing: F) -> Result<Self, PolicyError> {
+        if thresholds
+            .iter()
+            .any(|threshold| !(0. ..=1.).contains(threshold))
+        {
+            return Err(PolicyError::InvalidFractionError);
+        }
+
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_by(f64::total_cmp);
+        thresholds.dedup();
+
+        Ok(Self {
+            inner,
+            last_fired_at: vec![None; thresholds.len()],
+            thresholds,
+            debounce: Duration::zero(),
+            on_crossing,
+        })
+    }
+
+    /// Keeps a threshold from firing again within `debounce` of its last crossing.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn observe(&mut self, tokens: u64, rate_limit: &RateLimit) {
+        if rate_limit.limit == 0 {
+            return;
+        }
+
+        let used = rate_limit.limit.saturating_sub(rate_limit.available_tokens);
+        let usage = used as f64 / rate_limit.limit as f64;
+        let previous_usage = used.saturating_sub(tokens) as f64 / rate_limit.limit as f64;
+        let now = self.inner.now();
+
+        for (threshold, last_fired_at) in self.thresholds.iter().zip(self.last_fired_at.iter_mut())
+        {
+            let debounced =
+                last_fired_at.is_some_and(|last_fired_at| now - last_fired_at < self.debounce);
+
+            if previous_usage < *threshold && usage >= *threshold && !debounced {
+                *last_fired_at = Some(now);
+                (self.on_crossing)(&ThresholdCrossing {
+                    threshold: *threshold,
+                    rate_limit: rate_limit.clone(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FixedWindowPolicy;
+    use crate::storage::InMemoryStorage;
+    use crate::{Duration, MockClock};
+    use std::cell::RefCell;
+
+    #[test]
+    fn fires_each_threshold_once_per_window() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let inner = FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+        let fired = RefCell::new(Vec::new());
+        let mut policy = ThresholdPolicy::new(inner, &[1., 0.5, 0.8], |crossing| {
+            fired.borrow_mut().push(crossing.threshold)
+        })
+        .unwrap();
+
+        policy.consume(5).unwrap();
+        policy.consume(1).unwrap();
+        assert_eq!(*fired.borrow(), [0.5]);
+
+        policy.consume(4).unwrap();
+        policy.consume(1).unwrap();
+        assert_eq!(*fired.borrow(), [0.5, 0.8, 1.]);
+
+        clock.advance(Duration::minutes(1));
+        policy.consume(6).unwrap();
+        assert_eq!(*fired.borrow(), [0.5, 0.8, 1., 0.5]);
+    }
+
+    #[test]
+    fn debounces_repeated_crossings() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let inner = FixedWindowPolicy::new(10, "key".into(), Duration::minutes(1), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+        let fired = RefCell::new(0);
+        let mut policy = ThresholdPolicy::new(inner, &[0.5], |_| *fired.borrow_mut() += 1)
+            .unwrap()
+            .with_debounce(Duration::minutes(5));
+
+        for _ in 0..3 {
+            policy.consume(5).unwrap();
+            policy.refund(5);
+        }
+        assert_eq!(*fired.borrow(), 1);
+
+        clock.advance(Duration::minutes(5));
+        policy.consume(5).unwrap();
+        assert_eq!(*fired.borrow(), 2);
+    }
+}
@@ -0,0 +1,395 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::{Policy, Rate};
+use crate::storage::{State, Storage};
+use crate::{
+    ChronoTimestampMillis, Clock, Duration, LocalDateTime, LocalTime, RateLimit, Reservation,
+    SystemClock, Unit,
+};
+use chrono::TimeZone;
+
+pub struct TokenBucketPolicy<'a, Store: Storage<TokenBucketState, TokenBucketState>> {
+    burst: u64,
+    key: String,
+    rate: Rate,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Box<dyn Clock>,
+}
+
+impl<Store: Storage<TokenBucketState, TokenBucketState>> Policy for TokenBucketPolicy<'_, Store> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        if tokens > self.burst {
+            // Cannot reserve more tokens than the size of the rate limiter.
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.burst,
+            });
+        }
+
+        let now = self.clock.now();
+        let mut state = self.load_state(&now);
+        let available_tokens = state.get_available_tokens();
+
+        let reservation = if tokens == 0 {
+            let retry_after = if available_tokens > 0 {
+                now
+            } else {
+                LocalTime::timestamp_millis_opt(
+                    &LocalTime,
+                    now.timestamp_millis() + state.calculate_time_for_tokens(1, &now),
+                )
+                .unwrap()
+            };
+
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens,
+                    retry_after,
+                    accepted: true,
+                    limit: self.burst,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else if available_tokens >= tokens {
+            state.take(tokens, &now);
+            Reservation {
+                time_to_act: now,
+                rate_limit: RateLimit {
+                    available_tokens: state.get_available_tokens(),
+                    retry_after: now,
+                    accepted: true,
+                    limit: self.burst,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        } else {
+            let wait_duration = state.calculate_time_for_tokens(tokens, &now);
+            let retry_after =
+                LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + wait_duration)
+                    .unwrap();
+
+            if let Some(max_time) = max_time {
+                if wait_duration > max_time {
+                    return Err(ReserveError::MaxWaitDurationExceededError {
+                        rate_limit: RateLimit {
+                            available_tokens,
+                            retry_after,
+                            accepted: false,
+                            limit: self.burst,
+                            reset_at: state.get_reset_time(&now),
+                            unit: self.unit.clone(),
+                        },
+                    });
+                }
+            }
+
+            // The tokens are borrowed from future refills, so nobody else
+            // gets them before this reservation acts.
+            state.take(tokens, &now);
+
+            Reservation {
+                time_to_act: retry_after,
+                rate_limit: RateLimit {
+                    available_tokens: 0,
+                    retry_after,
+                    accepted: false,
+                    limit: self.burst,
+                    reset_at: state.get_reset_time(&now),
+                    unit: self.unit.clone(),
+                },
+            }
+        };
+
+        if tokens > 0 {
+            self.storage.save(&self.key, state);
+        }
+
+        Ok(reservation)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        let now = self.clock.now();
+
+        if let Some(mut state) = self.storage.fetch(self.key.as_str()) {
+            state.refill(&now);
+            state.refund(tokens, &now);
+            self.storage.save(&self.key, state);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<TokenBucketState, TokenBucketState>> TokenBucketPolicy<'a, Store> {
+    /// Creates a bucket holding at most `burst` tokens, refilled at `rate`.
+    /// A new key starts with a full bucket.
+    pub fn new(
+        burst: u64,
+        key: String,
+        rate: Rate,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if burst == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        if !rate.is_valid() {
+            return Err(PolicyError::InvalidRateError);
+        }
+
+        Ok(Self {
+            burst,
+            key,
+            rate,
+            storage,
+            unit: Unit::default(),
+            clock: Box::new(SystemClock),
+        })
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Returns how long until `tokens` tokens are available, without recording anything.
+    pub fn time_until_available(&self, tokens: u64) -> Result<Duration, ReserveError> {
+        if tokens > self.burst {
+            return Err(ReserveError::TooManyTokensError {
+                requested: tokens,
+                max: self.burst,
+            });
+        }
+
+        let now = self.clock.now();
+        let wait = self
+            .load_state(&now)
+            .calculate_time_for_tokens(tokens, &now);
+
+        Ok(Duration::milliseconds(wait))
+    }
+
+    /// Fetches the state of the key and refills it up to `now`.
+    fn load_state(&self, now: &LocalDateTime) -> TokenBucketState {
+        let mut state = self
+            .storage
+            .fetch(self.key.as_str())
+            .unwrap_or_else(|| TokenBucketState::new(self.key.clone(), self.burst, self.rate, now));
+
+        state.refill(now);
+
+        state
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenBucketState {
+    pub key: String,
+    /// Negative while reservations are waiting for future refills.
+    pub tokens: i64,
+    pub burst_size: u64,
+    pub rate: Rate,
+    /// When the last refill happened; partial refill progress is counted from here.
+    pub timer: ChronoTimestampMillis,
+    pub created_at: Option<ChronoTimestampMillis>,
+    pub last_hit_at: Option<ChronoTimestampMillis>,
+}
+
+impl State<TokenBucketState> for TokenBucketState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        u64::try_from(self.rate.calculate_time_for_tokens(self.burst_size)).unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.created_at
+            .and_then(|created_at| LocalTime.timestamp_millis_opt(created_at).single())
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.last_hit_at
+            .and_then(|last_hit_at| LocalTime.timestamp_millis_opt(last_hit_at).single())
+    }
+}
+
+impl TokenBucketState {
+    pub fn new(key: String, burst_size: u64, rate: Rate, now: &LocalDateTime) -> Self {
+        Self {
+            key,
+            tokens: i64::try_from(burst_size).unwrap_or(i64::MAX),
+            burst_size,
+            rate,
+            timer: now.timestamp_millis(),
+            created_at: None,
+            last_hit_at: None,
+        }
+    }
+
+    /// Adds the tokens refilled since the last refill, up to the burst size.
+    pub fn refill(&mut self, now: &LocalDateTime) {
+        let now = now.timestamp_millis();
+        let burst_size = i64::try_from(self.burst_size).unwrap_or(i64::MAX);
+        let new_tokens = self
+            .rate
+            .calculate_new_tokens_during_interval(now - self.timer);
+
+        if new_tokens == 0 {
+            return;
+        }
+
+        self.tokens = self
+            .tokens
+            .saturating_add(i64::try_from(new_tokens).unwrap_or(i64::MAX))
+            .min(burst_size);
+
+        if self.tokens == burst_size {
+            // A full bucket does not bank refill progress.
+            self.timer = now;
+        } else {
+            self.timer += self.rate.calculate_time_for_tokens(new_tokens);
+        }
+    }
+
+    pub fn take(&mut self, tokens: u64, now: &LocalDateTime) {
+        self.tokens = self
+            .tokens
+            .saturating_sub(i64::try_from(tokens).unwrap_or(i64::MAX));
+        self.created_at.get_or_insert(now.timestamp_millis());
+        self.last_hit_at = Some(now.timestamp_millis());
+    }
+
+    /// Puts `tokens` back into the bucket, up to the burst size. Expects a refilled state.
+    pub fn refund(&mut self, tokens: u64, now: &LocalDateTime) {
+        let burst_size = i64::try_from(self.burst_size).unwrap_or(i64::MAX);
+
+        self.tokens = self
+            .tokens
+            .saturating_add(i64::try_from(tokens).unwrap_or(i64::MAX))
+            .min(burst_size);
+
+        if self.tokens == burst_size {
+            // A full bucket does not bank refill progress.
+            self.timer = now.timestamp_millis();
+        }
+    }
+
+    pub fn get_available_tokens(&self) -> u64 {
+        u64::try_from(self.tokens).unwrap_or(0)
+    }
+
+    /// Returns the milliseconds until `tokens` tokens are available. Expects a refilled state.
+    pub fn calculate_time_for_tokens(&self, tokens: u64, now: &LocalDateTime) -> i64 {
+        let missing = i64::try_from(tokens)
+            .unwrap_or(i64::MAX)
+            .saturating_sub(self.tokens);
+
+        if missing <= 0 {
+            return 0;
+        }
+
+        let progress = now.timestamp_millis() - self.timer;
+        let refill_time = self.rate.calculate_time_for_tokens(missing.unsigned_abs());
+
+        (refill_time - progress).max(0)
+    }
+
+    /// Returns the moment the bucket is full again.
+    pub fn get_reset_time(&self, now: &LocalDateTime) -> LocalDateTime {
+        let wait = self.calculate_time_for_tokens(self.burst_size, now);
+
+        LocalTime::timestamp_millis_opt(&LocalTime, now.timestamp_millis() + wait).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::conformance;
+    use crate::storage::InMemoryStorage;
+    use crate::{Duration, MockClock};
+
+    #[test]
+    fn token_bucket_policy_conforms() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = TokenBucketPolicy::new(5, "key".into(), Rate::per_minute(5), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+
+        conformance::run(
+            &mut policy,
+            &clock,
+            5,
+            Duration::minutes(1),
+            5,
+            Duration::minutes(1),
+        );
+    }
+
+    #[test]
+    fn refunds_up_to_the_burst_size() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = TokenBucketPolicy::new(4, "key".into(), Rate::per_minute(1), &mut storage)
+            .unwrap()
+            .with_clock(clock.clone());
+
+        assert!(policy.consume(4).unwrap().rate_limit.is_accepted());
+        policy.refund(2);
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 2);
+
+        policy.refund(10);
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 4);
+    }
+
+    #[test]
+    fn refills_at_the_configured_rate() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy =
+            TokenBucketPolicy::new(10, "key".into(), Rate::per_second(2), &mut storage)
+                .unwrap()
+                .with_clock(clock.clone());
+
+        assert!(policy.consume(10).unwrap().rate_limit.is_accepted());
+
+        let rejected = policy.consume(3).unwrap().rate_limit;
+        assert!(!rejected.is_accepted());
+        assert_eq!(
+            rejected.get_retry_after(),
+            clock.now() + Duration::seconds(2)
+        );
+
+        clock.advance(Duration::milliseconds(1500));
+        assert_eq!(
+            policy.consume(2).unwrap().rate_limit.get_remaining_tokens(),
+            0
+        );
+
+        clock.advance(Duration::milliseconds(500));
+        assert_eq!(policy.peek().unwrap().get_remaining_tokens(), 2);
+    }
+}
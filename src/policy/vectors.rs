@@ -0,0 +1,267 @@
+//! Language-agnostic test vectors for the window calculations.
+//!
+//! Implementations in other languages sharing a storage with this crate can
+//! replay the vectors to prove they take the same decisions and store the same
+//! states. The data files are
+//! published in the `vectors` directory of the crate, in a line based format
+//! documented at their top, and bundled here as [`FIXED_WINDOW`] and
+//! [`SLIDING_WINDOW`].
+
+use crate::error::{ReserveError, VectorError};
+use crate::policy::{FixedWindowPolicy, Policy, SlidingWindowPolicy};
+use crate::storage::{InMemoryStorage, Storage};
+use crate::{Duration, LocalDateTime, LocalTime, MockClock};
+use chrono::TimeZone;
+
+/// The vectors of the fixed window policy, windows starting at the first hit.
+pub const FIXED_WINDOW: &str = include_str!("../../vectors/fixed_window.txt");
+
+/// The vectors of the sliding window policy.
+pub const SLIDING_WINDOW: &str = include_str!("../../vectors/sliding_window.txt");
+
+/// The policy a [`Vector`] exercises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    FixedWindow,
+    SlidingWindow,
+}
+
+/// What a consume decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub accepted: bool,
+    pub remaining: u64,
+}
+
+/// The state stored for the key after a consume, times as offsets from the start of the vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoredState {
+    /// The hits of the current window and when it started.
+    FixedWindow { hit_count: u64, timer: Duration },
+    /// The hits of the current and the previous window, and when the current one ends.
+    SlidingWindow {
+        hit_count: u64,
+        previous_hit_count: u64,
+        window_end_at: Duration,
+    },
+}
+
+/// A consume of `tokens` at `offset` after the start of the vector, with its
+/// expected decision and the state stored after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub offset: Duration,
+    pub tokens: u64,
+    pub expected: Decision,
+    pub expected_state: StoredState,
+}
+
+/// A sequence of consumes on a fresh key, with the decisions they must get.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vector {
+    pub name: String,
+    pub algorithm: Algorithm,
+    pub limit: u64,
+    pub interval: Duration,
+    pub start: LocalDateTime,
+    pub steps: Vec<Step>,
+}
+
+impl Vector {
+    /// Replays the steps through `decide`, which consumes the tokens at the given
+    /// time and returns the decision with the state then stored for the key, and
+    /// fails on the first decision or state that differs from the expected one.
+    pub fn verify<F>(&self, mut decide: F) -> Result<(), VectorError>
+    where
+        F: FnMut(LocalDateTime, u64) -> Result<(Decision, Option<StoredState>), ReserveError>,
+    {
+        for (index, step) in self.steps.iter().enumerate() {
+            let (actual, actual_state) = decide(self.start + step.offset, step.tokens)?;
+
+            if actual != step.expected {
+                return Err(VectorError::MismatchError {
+                    vector: self.name.clone(),
+                    step: index + 1,
+                    expected: step.expected,
+                    actual,
+                });
+            }
+
+            if actual_state != Some(step.expected_state) {
+                return Err(VectorError::StateMismatchError {
+                    vector: self.name.clone(),
+                    step: index + 1,
+                    expected: step.expected_state,
+                    actual: actual_state,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replays the steps through the policy of this crate the vector exercises.
+    pub fn verify_policy(&self) -> Result<(), VectorError> {
+        let clock = MockClock::new(self.start);
+        let key = String::from("vector");
+        let since_start = |at: i64| Duration::milliseconds(at - self.start.timestamp_millis());
+        let decide = |policy: &mut dyn Policy, tokens| {
+            let rate_limit = policy.consume(tokens)?.rate_limit;
+
+            Ok::<_, ReserveError>(Decision {
+                accepted: rate_limit.is_accepted(),
+                remaining: rate_limit.get_remaining_tokens(),
+            })
+        };
+
+        // The policy is built for every step, so the storage can be read in between.
+        match self.algorithm {
+            Algorithm::FixedWindow => {
+                let mut storage = InMemoryStorage::new();
+
+                self.verify(|at, tokens| {
+                    clock.set(at);
+                    let decision = decide(
+                        &mut FixedWindowPolicy::new(
+                            self.limit,
+                            key.clone(),
+                            self.interval,
+                            &mut storage,
+                        )?
+                        .with_clock(clock.clone()),
+                        tokens,
+                    )?;
+                    let state = storage.fetch(&key).map(|state| StoredState::FixedWindow {
+                        hit_count: state.hit_count,
+                        timer: since_start(state.timer),
+                    });
+
+                    Ok((decision, state))
+                })
+            }
+            Algorithm::SlidingWindow => {
+                let mut storage = InMemoryStorage::new();
+
+                self.verify(|at, tokens| {
+                    clock.set(at);
+                    let decision = decide(
+                        &mut SlidingWindowPolicy::new(
+                            self.limit,
+                            key.clone(),
+                            self.interval,
+                            &mut storage,
+                        )?
+                        .with_clock(clock.clone()),
+                        tokens,
+                    )?;
+                    let state = storage.fetch(&key).map(|state| StoredState::SlidingWindow {
+                        hit_count: state.get_current_window_hits(),
+                        previous_hit_count: state.get_previous_window_hits(),
+                        window_end_at: since_start(state.window_end_at),
+                    });
+
+                    Ok((decision, state))
+                })
+            }
+        }
+    }
+}
+
+/// Parses the vectors of a data file such as [`FIXED_WINDOW`].
+pub fn parse(data: &str) -> Result<Vec<Vector>, VectorError> {
+    let mut vectors: Vec<Vector> = Vec::new();
+
+    for (index, line) in data.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let malformed = || VectorError::MalformedLineError(line_number);
+
+        match fields.as_slice() {
+            ["vector", name, algorithm, limit, interval, start] => {
+                let algorithm = match *algorithm {
+                    "fixed_window" => Algorithm::FixedWindow,
+                    "sliding_window" => Algorithm::SlidingWindow,
+                    _ => return Err(VectorError::UnknownAlgorithmError(algorithm.to_string())),
+                };
+                let start = LocalTime
+                    .timestamp_millis_opt(start.parse().map_err(|_| malformed())?)
+                    .single()
+                    .ok_or_else(malformed)?;
+
+                vectors.push(Vector {
+                    name: name.to_string(),
+                    algorithm,
+                    limit: limit.parse().map_err(|_| malformed())?,
+                    interval: Duration::milliseconds(interval.parse().map_err(|_| malformed())?),
+                    start,
+                    steps: Vec::new(),
+                });
+            }
+            [offset, tokens, decision, remaining, state @ ..] => {
+                let vector = vectors.last_mut().ok_or_else(malformed)?;
+                let accepted = match *decision {
+                    "accepted" => true,
+                    "rejected" => false,
+                    _ => return Err(malformed()),
+                };
+                let number = |field: &str| field.parse::<u64>().map_err(|_| malformed());
+                let millis = |field: &str| {
+                    field
+                        .parse()
+                        .map(Duration::milliseconds)
+                        .map_err(|_| malformed())
+                };
+                let expected_state = match (vector.algorithm, state) {
+                    (Algorithm::FixedWindow, [hit_count, timer]) => StoredState::FixedWindow {
+                        hit_count: number(hit_count)?,
+                        timer: millis(timer)?,
+                    },
+                    (Algorithm::SlidingWindow, [hit_count, previous_hit_count, window_end_at]) => {
+                        StoredState::SlidingWindow {
+                            hit_count: number(hit_count)?,
+                            previous_hit_count: number(previous_hit_count)?,
+                            window_end_at: millis(window_end_at)?,
+                        }
+                    }
+                    _ => return Err(malformed()),
+                };
+
+                vector.steps.push(Step {
+                    offset: millis(offset)?,
+                    tokens: number(tokens)?,
+                    expected: Decision {
+                        accepted,
+                        remaining: number(remaining)?,
+                    },
+                    expected_state,
+                });
+            }
+            _ => return Err(malformed()),
+        }
+    }
+
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies_match_the_published_vectors() {
+        for data in [FIXED_WINDOW, SLIDING_WINDOW] {
+            let vectors = parse(data).unwrap();
+            assert!(!vectors.is_empty());
+
+            for vector in vectors {
+                vector.verify_policy().unwrap();
+            }
+        }
+    }
+}
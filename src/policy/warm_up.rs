@@ -0,0 +1,158 @@
+use crate::error::{PolicyError, ReserveError};
+use crate::policy::{FixedWindowPolicy, FixedWindowState, Policy};
+use crate::storage::{State, Storage};
+use crate::{Clock, Duration, LocalDateTime, Reservation, SystemClock, Unit};
+use std::sync::Arc;
+
+/// A fixed window whose limit ramps up after a key first appears.
+///
+/// The limit starts at `floor` on the first hit of a key and grows linearly to
+/// `limit` over the warm-up period, protecting cold caches behind the limited
+/// service. A key whose state is gone, e.g. expired from the storage, warms up again.
+pub struct WarmUpPolicy<'a, Store: Storage<FixedWindowState, FixedWindowState>> {
+    floor: u64,
+    limit: u64,
+    warm_up: Duration,
+    key: String,
+    interval: Duration,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Arc<dyn Clock>,
+}
+
+impl<Store: Storage<FixedWindowState, FixedWindowState>> Policy for WarmUpPolicy<'_, Store> {
+    fn reserve(&mut self, tokens: u64, max_time: Option<i64>) -> Result<Reservation, ReserveError> {
+        let limit = self.get_limit();
+
+        FixedWindowPolicy::new(limit, self.key.clone(), self.interval, &mut *self.storage)
+            .expect("the limit and the key are validated by WarmUpPolicy::new")
+            .with_unit(self.unit.clone())
+            .with_clock(Arc::clone(&self.clock))
+            .reserve(tokens, max_time)
+    }
+
+    fn refund(&mut self, tokens: u64) {
+        let limit = self.get_limit();
+
+        FixedWindowPolicy::new(limit, self.key.clone(), self.interval, &mut *self.storage)
+            .expect("the limit and the key are validated by WarmUpPolicy::new")
+            .with_clock(Arc::clone(&self.clock))
+            .refund(tokens);
+    }
+
+    fn reset(&mut self) {
+        self.storage.delete(&self.key);
+    }
+
+    fn now(&self) -> LocalDateTime {
+        self.clock.now()
+    }
+}
+
+impl<'a, Store: Storage<FixedWindowState, FixedWindowState>> WarmUpPolicy<'a, Store> {
+    /// Allows `limit` tokens per `interval` once warmed up, starting from `floor`
+    /// tokens per `interval` and ramping up over `warm_up`.
+    pub fn new(
+        floor: u64,
+        limit: u64,
+        warm_up: Duration,
+        key: String,
+        interval: Duration,
+        storage: &'a mut Store,
+    ) -> Result<Self, PolicyError> {
+        if floor == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if floor > limit {
+            return Err(PolicyError::InvalidLimitRangeError);
+        }
+
+        if key.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        Ok(Self {
+            floor,
+            limit,
+            warm_up,
+            key,
+            interval,
+            storage,
+            unit: Unit::default(),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Sets the unit reported by the rate limits of this policy.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Replaces the clock the policy reads the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Returns the limit the key is currently allowed.
+    pub fn get_limit(&self) -> u64 {
+        let created_at = self
+            .storage
+            .fetch(self.key.as_str())
+            .and_then(|state| state.get_created_at());
+
+        let Some(created_at) = created_at else {
+            return self.floor;
+        };
+
+        let elapsed = (self.clock.now() - created_at).num_milliseconds();
+        let warm_up = self.warm_up.num_milliseconds();
+
+        if elapsed >= warm_up {
+            return self.limit;
+        }
+
+        let progress = elapsed.max(0) as f64 / warm_up as f64;
+
+        self.floor + ((self.limit - self.floor) as f64 * progress).floor() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn limit_ramps_up_from_the_first_hit() {
+        let clock = MockClock::default();
+        let mut storage = InMemoryStorage::new();
+        let mut policy = WarmUpPolicy::new(
+            2,
+            10,
+            Duration::minutes(4),
+            "key".into(),
+            Duration::minutes(1),
+            &mut storage,
+        )
+        .unwrap()
+        .with_clock(clock.clone());
+
+        assert_eq!(policy.get_limit(), 2);
+        assert!(policy.consume(2).unwrap().rate_limit.is_accepted());
+        assert!(!policy.consume(1).unwrap().rate_limit.is_accepted());
+
+        clock.advance(Duration::minutes(2));
+        assert_eq!(policy.get_limit(), 6);
+        assert_eq!(
+            policy.consume(1).unwrap().rate_limit.get_remaining_tokens(),
+            5
+        );
+
+        clock.advance(Duration::minutes(3));
+        assert_eq!(policy.get_limit(), 10);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{Duration, LocalDateTime, LocalTime};
+use chrono::{DateTime, TimeZone};
+
+/// Unix timestamps (in seconds) are told apart from delta-seconds by their size.
+const UNIX_TIMESTAMP_THRESHOLD: i64 = 1_000_000_000;
+
+/// Client side counterpart of the limiter.
+///
+/// Feed it the rate limit headers of every upstream response and it answers
+/// whether the next request can be sent now, or when it should be sent.
+/// Understands `RateLimit-*` and `X-RateLimit-*` headers, the structured
+/// `RateLimit` header and `Retry-After`.
+#[derive(Debug, Default, Clone)]
+pub struct QuotaTracker {
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset_at: Option<LocalDateTime>,
+    retry_after: Option<LocalDateTime>,
+}
+
+impl QuotaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the tracker from the headers of an upstream response.
+    /// Header names are matched case-insensitively, unknown headers are ignored.
+    pub fn ingest<'h, I>(&mut self, headers: I)
+    where
+        I: IntoIterator<Item = (&'h str, &'h str)>,
+    {
+        let now = LocalTime::now();
+
+        for (name, value) in headers {
+            let value = value.trim();
+
+            match name.to_ascii_lowercase().as_str() {
+                "ratelimit-limit" | "x-ratelimit-limit" => {
+                    self.limit = parse_leading_number(value).or(self.limit);
+                }
+                "ratelimit-remaining" | "x-ratelimit-remaining" => {
+                    self.remaining = parse_leading_number(value).or(self.remaining);
+                }
+                "ratelimit-reset" | "x-ratelimit-reset" => {
+                    self.reset_at = parse_reset(value, &now).or(self.reset_at);
+                }
+                "ratelimit" => self.ingest_structured(value, &now),
+                "retry-after" => {
+                    self.retry_after = parse_retry_after(value, &now).or(self.retry_after);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Records a request sent without a response yet, so that bursts between
+    /// responses do not overshoot the last known remaining quota.
+    pub fn record_sent(&mut self) {
+        self.refresh(&LocalTime::now());
+
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining = remaining.saturating_sub(1);
+        }
+    }
+
+    pub fn can_send_now(&self) -> bool {
+        self.next_send_at() <= LocalTime::now()
+    }
+
+    /// Returns the earliest time the next request should be sent.
+    pub fn next_send_at(&self) -> LocalDateTime {
+        let now = LocalTime::now();
+
+        if let Some(retry_after) = self.retry_after {
+            if retry_after > now {
+                return retry_after;
+            }
+        }
+
+        match (self.remaining, self.reset_at) {
+            (Some(0), Some(reset_at)) if reset_at > now => reset_at,
+            _ => now,
+        }
+    }
+
+    /// Returns how long the caller should wait before sending the next request.
+    pub fn time_until_next_send(&self) -> Duration {
+        (self.next_send_at() - LocalTime::now()).max(Duration::zero())
+    }
+
+    /// Returns the last known remaining quota, if the upstream reported one.
+    pub fn get_remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    /// Returns the last known limit, if the upstream reported one.
+    pub fn get_limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Returns when the upstream said its window resets, if it did.
+    pub fn get_reset_at(&self) -> Option<LocalDateTime> {
+        self.reset_at
+    }
+
+    fn ingest_structured(&mut self, value: &str, now: &LocalDateTime) {
+        for item in value.split([',', ';']) {
+            let Some((name, value)) = item.split_once('=') else {
+                continue;
+            };
+
+            match name.trim() {
+                "limit" | "l" => self.limit = parse_leading_number(value).or(self.limit),
+                "remaining" | "r" => {
+                    self.remaining = parse_leading_number(value).or(self.remaining)
+                }
+                "reset" | "t" => self.reset_at = parse_reset(value, now).or(self.reset_at),
+                _ => {}
+            }
+        }
+    }
+
+    /// Assumes the full limit is available again once the reported window has reset.
+    fn refresh(&mut self, now: &LocalDateTime) {
+        if let Some(reset_at) = self.reset_at {
+            if reset_at <= *now {
+                self.remaining = self.limit;
+                self.reset_at = None;
+            }
+        }
+    }
+}
+
+/// Parses values such as `100` or `100;w=60` (the quota policy suffix is ignored).
+fn parse_leading_number(value: &str) -> Option<u64> {
+    value
+        .trim()
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|number| number.parse().ok())
+}
+
+/// Reset values are delta-seconds, unless they are large enough to be a unix timestamp.
+fn parse_reset(value: &str, now: &LocalDateTime) -> Option<LocalDateTime> {
+    let seconds = i64::try_from(parse_leading_number(value)?).ok()?;
+
+    if seconds >= UNIX_TIMESTAMP_THRESHOLD {
+        return LocalTime.timestamp_opt(seconds, 0).single();
+    }
+
+    now.checked_add_signed(Duration::try_seconds(seconds)?)
+}
+
+/// `Retry-After` is either delta-seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: &LocalDateTime) -> Option<LocalDateTime> {
+    if let Some(seconds) = parse_leading_number(value) {
+        return now.checked_add_signed(Duration::try_seconds(i64::try_from(seconds).ok()?)?);
+    }
+
+    DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| date.with_timezone(&LocalTime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_reset_once_quota_is_exhausted() {
+        let mut tracker = QuotaTracker::new();
+        assert!(tracker.can_send_now());
+
+        tracker.ingest([
+            ("X-RateLimit-Limit", "10"),
+            ("X-RateLimit-Remaining", "1"),
+            ("X-RateLimit-Reset", "30"),
+        ]);
+        assert!(tracker.can_send_now());
+
+        tracker.record_sent();
+        assert_eq!(tracker.get_remaining(), Some(0));
+        assert!(!tracker.can_send_now());
+        assert!(tracker.time_until_next_send() > Duration::seconds(25));
+    }
+
+    #[test]
+    fn honors_retry_after_and_structured_header() {
+        let mut tracker = QuotaTracker::new();
+        tracker.ingest([("RateLimit", "limit=100, remaining=50, reset=60")]);
+
+        assert_eq!(tracker.get_limit(), Some(100));
+        assert_eq!(tracker.get_remaining(), Some(50));
+        assert!(tracker.can_send_now());
+
+        tracker.ingest([("retry-after", "5")]);
+        assert!(!tracker.can_send_now());
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Source of the random numbers behind sampling, load shedding and jitter.
+///
+/// Policies use an OS-seeded [`XorShiftRandom`] unless configured otherwise.
+/// Supply a seeded source for reproducible tests and simulations, or your own
+/// source on targets without OS randomness.
+pub trait RandomSource: Send {
+    fn next_u64(&mut self) -> u64;
+
+    /// Returns a number in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// State used when a seed mixes to zero.
+const ZERO_SEED_STATE: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// Small xorshift64* generator, good enough for sampling and jitter decisions.
+#[derive(Debug, Clone)]
+pub struct XorShiftRandom {
+    state: u64,
+}
+
+impl Default for XorShiftRandom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XorShiftRandom {
+    /// Creates a generator seeded by the OS.
+    pub fn new() -> Self {
+        // RandomState is seeded by the OS, which saves us a dependency on `rand`.
+        Self::from_seed(RandomState::new().build_hasher().finish())
+    }
+
+    /// Creates a generator producing the same numbers for the same seed.
+    ///
+    /// The seed is mixed with splitmix64 first, so close seeds give unrelated
+    /// streams, and a zero state, which xorshift never leaves, is replaced.
+    pub fn from_seed(seed: u64) -> Self {
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        state ^= state >> 31;
+
+        Self {
+            state: if state == 0 { ZERO_SEED_STATE } else { state },
+        }
+    }
+}
+
+impl RandomSource for XorShiftRandom {
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_generators_repeat_themselves() {
+        let (mut first, mut second) =
+            (XorShiftRandom::from_seed(42), XorShiftRandom::from_seed(42));
+
+        for _ in 0..10 {
+            let number = first.next_f64();
+            assert_eq!(number, second.next_f64());
+            assert!((0. ..1.).contains(&number));
+        }
+    }
+
+    #[test]
+    fn close_seeds_give_different_streams() {
+        let streams: Vec<Vec<u64>> = (0..4)
+            .map(|seed| {
+                let mut random = XorShiftRandom::from_seed(seed);
+                (0..4).map(|_| random.next_u64()).collect()
+            })
+            .collect();
+
+        for (index, stream) in streams.iter().enumerate() {
+            assert!(stream.iter().all(|number| *number != 0));
+            assert!(!streams[index + 1..].contains(stream));
+        }
+    }
+}
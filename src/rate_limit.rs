@@ -1,26 +1,41 @@
 use crate::error::RateLimitExceededError;
-use crate::LocalDateTime;
+use crate::{Duration, LocalDateTime, Unit};
 
 /// A structure containing information about
 /// the current speed limit for a particular key.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RateLimit {
-    pub(crate) available_tokens: usize,
+    pub(crate) available_tokens: u64,
     pub(crate) retry_after: LocalDateTime,
     pub(crate) accepted: bool,
-    pub(crate) limit: usize,
+    pub(crate) limit: u64,
+    pub(crate) reset_at: LocalDateTime,
+    pub(crate) unit: Unit,
 }
 
 impl RateLimit {
     /// Returns the number of tokens available.
-    pub fn get_remaining_tokens(&self) -> usize {
+    pub fn get_remaining_tokens(&self) -> u64 {
         self.available_tokens
     }
 
     /// If the tokens have run out, this method will return the time after which
     /// at least one token will be available.
     pub fn get_retry_after(&self) -> LocalDateTime {
-        self.retry_after.clone()
+        self.retry_after
+    }
+
+    /// Returns the time at which the whole limit becomes available again.
+    pub fn get_reset_at(&self) -> LocalDateTime {
+        self.reset_at
+    }
+
+    /// Returns how long is left at `now` until [`Self::get_reset_at()`],
+    /// or zero if it has already passed.
+    ///
+    /// Pass the time of the policy clock that made the decision.
+    pub fn time_until_reset(&self, now: &LocalDateTime) -> Duration {
+        (self.reset_at - *now).max(Duration::zero())
     }
 
     /// Returns a result reflecting whether this request was executed within the current limit.
@@ -29,10 +44,15 @@ impl RateLimit {
     }
 
     /// TODO doc
-    pub fn get_limit(&self) -> usize {
+    pub fn get_limit(&self) -> u64 {
         self.limit
     }
 
+    /// Returns the unit in which the tokens of this rate limit are expressed.
+    pub fn get_unit(&self) -> &Unit {
+        &self.unit
+    }
+
     /// Same as [`Self::is_accepted()`], but will return Err(RateLimitExceededError) if
     /// the request failed within the current limit.
     pub fn ensure_accepted(&self) -> Result<(), RateLimitExceededError> {
@@ -0,0 +1,207 @@
+use crate::{Duration, LocalDateTime, LocalTime, Reservation};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs closures at the time to act of their reservation, so callers that
+/// reserve ahead need no delayed execution of their own.
+///
+/// The jobs run one after another on a single worker thread, in the order of
+/// their time to act, and should hand long work off to other threads. The time
+/// to act is compared with the system time. A job that panics does not stop the
+/// worker, the jobs after it still run. Dropping the scheduler stops the worker;
+/// jobs that are not due yet are dropped without running.
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BinaryHeap<Reverse<Scheduled>>,
+    next_id: u64,
+    stopped: bool,
+}
+
+struct Scheduled {
+    time_to_act: LocalDateTime,
+    // Keeps jobs acting at the same time in the order they were scheduled.
+    id: u64,
+    job: Job,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        (self.time_to_act, self.id) == (other.time_to_act, other.id)
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.time_to_act, self.id).cmp(&(other.time_to_act, other.id))
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    /// Starts the worker thread.
+    pub fn new() -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            changed: Condvar::new(),
+        });
+        let worker = {
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || shared.run())
+        };
+
+        Self {
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    /// Runs `job` at the time to act of `reservation`, or right away if it has passed.
+    pub fn schedule<F: FnOnce() + Send + 'static>(&self, reservation: &Reservation, job: F) {
+        self.schedule_at(*reservation.get_time_to_act(), job);
+    }
+
+    /// Runs `job` at `time_to_act`, or right away if it has passed.
+    pub fn schedule_at<F: FnOnce() + Send + 'static>(&self, time_to_act: LocalDateTime, job: F) {
+        let mut queue = self.shared.lock();
+        let id = queue.next_id;
+
+        queue.next_id += 1;
+        queue.jobs.push(Reverse(Scheduled {
+            time_to_act,
+            id,
+            job: Box::new(job),
+        }));
+        self.shared.changed.notify_one();
+    }
+
+    /// Returns the number of jobs that have not run yet.
+    pub fn pending(&self) -> usize {
+        self.shared.lock().jobs.len()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_one();
+
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn run(&self) {
+        let mut queue = self.lock();
+
+        loop {
+            if queue.stopped {
+                return;
+            }
+
+            let Some(Reverse(next)) = queue.jobs.peek() else {
+                queue = self
+                    .changed
+                    .wait(queue)
+                    .unwrap_or_else(PoisonError::into_inner);
+                continue;
+            };
+
+            let wait = next.time_to_act - LocalTime::now();
+
+            if wait > Duration::zero() {
+                // Woken up early when a sooner job is scheduled or the scheduler stops.
+                queue = self
+                    .changed
+                    .wait_timeout(queue, wait.to_std().unwrap_or_default())
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+                continue;
+            }
+
+            let Reverse(next) = queue.jobs.pop().unwrap();
+            drop(queue);
+            // The panic has been reported by the panic hook, the next jobs still run.
+            let _ = panic::catch_unwind(AssertUnwindSafe(next.job));
+            queue = self.lock();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn runs_jobs_in_time_to_act_order() {
+        let scheduler = Scheduler::new();
+        let (sender, receiver) = mpsc::channel();
+        let start = LocalTime::now();
+
+        for (name, delay) in [("late", 60), ("early", 20), ("due", -10)] {
+            let sender = sender.clone();
+            scheduler.schedule_at(start + Duration::milliseconds(delay), move || {
+                sender.send((name, LocalTime::now())).unwrap();
+            });
+        }
+
+        let runs: Vec<_> = receiver.iter().take(3).collect();
+        assert_eq!(
+            runs.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            ["due", "early", "late"]
+        );
+        assert!(runs[2].1 >= start + Duration::milliseconds(60));
+        assert_eq!(scheduler.pending(), 0);
+    }
+
+    #[test]
+    fn jobs_run_after_a_panicking_one() {
+        let scheduler = Scheduler::new();
+        let (sender, receiver) = mpsc::channel();
+        let now = LocalTime::now();
+
+        scheduler.schedule_at(now, || panic!("job failed"));
+        scheduler.schedule_at(now + Duration::milliseconds(10), move || {
+            sender.send(()).unwrap();
+        });
+
+        assert!(receiver
+            .recv_timeout(std::time::Duration::from_secs(5))
+            .is_ok());
+        assert_eq!(scheduler.pending(), 0);
+    }
+}
@@ -0,0 +1,188 @@
+//! One-liners for the common case: a thread-safe limiter over in-memory storage.
+//!
+//! ```
+//! use sf_rate_limiter::simple;
+//!
+//! let limiter = simple::per_minute(100).keyed();
+//! assert!(limiter.check("user:42"));
+//! ```
+//!
+//! The limiter uses [`SlidingWindowPolicy`] under the hood. Use the policies
+//! directly when you need another algorithm or your own storage.
+
+use crate::error::{LimiterError, PolicyError};
+use crate::policy::{Policy, SlidingWindowPolicy, SlidingWindowState};
+use crate::storage::InMemoryStorage;
+use crate::sync::Mutex;
+use crate::{Duration, RateLimit};
+
+/// Allows `limit` tokens per second.
+pub fn per_second(limit: u64) -> SimpleLimit {
+    per(limit, Duration::seconds(1))
+}
+
+/// Allows `limit` tokens per minute.
+pub fn per_minute(limit: u64) -> SimpleLimit {
+    per(limit, Duration::minutes(1))
+}
+
+/// Allows `limit` tokens per hour.
+pub fn per_hour(limit: u64) -> SimpleLimit {
+    per(limit, Duration::hours(1))
+}
+
+/// Allows `limit` tokens per day.
+pub fn per_day(limit: u64) -> SimpleLimit {
+    per(limit, Duration::days(1))
+}
+
+/// Allows `limit` tokens per `interval`.
+pub fn per(limit: u64, interval: Duration) -> SimpleLimit {
+    SimpleLimit { limit, interval }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SimpleLimit {
+    limit: u64,
+    interval: Duration,
+}
+
+impl SimpleLimit {
+    /// Creates a limiter applying this limit to every key separately.
+    pub fn keyed(self) -> KeyedLimiter {
+        KeyedLimiter {
+            limit: self.limit,
+            interval: self.interval,
+            storage: Mutex::new(InMemoryStorage::new()),
+        }
+    }
+}
+
+/// Thread-safe limiter applying the same limit to any number of keys.
+pub struct KeyedLimiter {
+    limit: u64,
+    interval: Duration,
+    storage: Mutex<InMemoryStorage<SlidingWindowState, SlidingWindowState>>,
+}
+
+impl KeyedLimiter {
+    /// Consumes `tokens` tokens for `key` if they are available right now.
+    pub fn consume(&self, key: &str, tokens: u64) -> Result<RateLimit, LimiterError> {
+        let mut storage = self.storage.lock();
+        let mut policy =
+            SlidingWindowPolicy::new(self.limit, key.to_string(), self.interval, &mut *storage)?;
+
+        Ok(policy.consume(tokens)?.rate_limit)
+    }
+
+    /// Consumes `tokens` tokens from every key in `keys`, or from none of them,
+    /// e.g. to limit a request by user, API key and IP at once.
+    ///
+    /// Returns the rate limit with the fewest remaining tokens when every key
+    /// accepts, otherwise the rate limit of the first key that rejects. The keys
+    /// are checked under one lock, so no other call sees a partial consume.
+    /// An empty `keys`, or an empty key, fails with [`PolicyError::EmptyKeyError`].
+    pub fn consume_many(&self, keys: &[&str], tokens: u64) -> Result<RateLimit, LimiterError> {
+        if keys.is_empty() || keys.iter().any(|key| key.is_empty()) {
+            return Err(PolicyError::EmptyKeyError.into());
+        }
+
+        let mut storage = self.storage.lock();
+        let mut consumed: Vec<&str> = Vec::with_capacity(keys.len());
+        let mut strictest: Option<RateLimit> = None;
+
+        for key in keys {
+            let mut policy = SlidingWindowPolicy::new(
+                self.limit,
+                key.to_string(),
+                self.interval,
+                &mut *storage,
+            )?;
+            let result = policy.consume(tokens);
+
+            let rate_limit = match result {
+                Ok(reservation) if reservation.rate_limit.is_accepted() => reservation.rate_limit,
+                result => {
+                    drop(policy);
+
+                    for key in consumed {
+                        SlidingWindowPolicy::new(
+                            self.limit,
+                            key.to_string(),
+                            self.interval,
+                            &mut *storage,
+                        )?
+                        .refund(tokens);
+                    }
+
+                    return Ok(result?.rate_limit);
+                }
+            };
+
+            consumed.push(key);
+            if strictest
+                .as_ref()
+                .is_none_or(|strictest| rate_limit.available_tokens < strictest.available_tokens)
+            {
+                strictest = Some(rate_limit);
+            }
+        }
+
+        Ok(strictest.expect("keys is not empty"))
+    }
+
+    /// Returns the current rate limit of `key` without consuming anything.
+    pub fn peek(&self, key: &str) -> Result<RateLimit, LimiterError> {
+        let mut storage = self.storage.lock();
+        let mut policy =
+            SlidingWindowPolicy::new(self.limit, key.to_string(), self.interval, &mut *storage)?;
+
+        Ok(policy.peek()?)
+    }
+
+    /// Consumes a single token for `key`, returning whether it was accepted.
+    /// Configuration errors count as rejections.
+    pub fn check(&self, key: &str) -> bool {
+        self.consume(key, 1)
+            .map(|rate_limit| rate_limit.is_accepted())
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_every_key_separately() {
+        let limiter = per_minute(2).keyed();
+
+        assert!(limiter.check("a"));
+        assert!(limiter.check("a"));
+        assert!(!limiter.check("a"));
+        assert!(limiter.check("b"));
+        assert_eq!(limiter.peek("b").unwrap().get_remaining_tokens(), 1);
+    }
+
+    #[test]
+    fn consume_many_is_all_or_nothing() {
+        let limiter = per_minute(2).keyed();
+        limiter.consume("ip", 2).unwrap();
+
+        assert!(!limiter
+            .consume_many(&["user", "ip"], 1)
+            .unwrap()
+            .is_accepted());
+        assert_eq!(limiter.peek("user").unwrap().get_remaining_tokens(), 2);
+
+        let rate_limit = limiter.consume_many(&["user", "key"], 1).unwrap();
+        assert!(rate_limit.is_accepted());
+        assert_eq!(limiter.peek("key").unwrap().get_remaining_tokens(), 1);
+    }
+
+    #[test]
+    fn keyed_limiter_is_thread_safe() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<KeyedLimiter>();
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{Duration, LocalDateTime};
+
+/// A read-only view of what a policy currently counts for a key, e.g. to show
+/// the usage per key on a dashboard.
+#[derive(Debug, Clone)]
+pub struct StateSnapshot {
+    pub(crate) hit_count: u64,
+    pub(crate) window_end: LocalDateTime,
+    pub(crate) limit: u64,
+    pub(crate) interval: Duration,
+}
+
+impl StateSnapshot {
+    /// Returns the number of tokens counted against the limit right now.
+    pub fn get_hit_count(&self) -> u64 {
+        self.hit_count
+    }
+
+    /// Returns the moment the current window ends.
+    pub fn get_window_end(&self) -> LocalDateTime {
+        self.window_end
+    }
+
+    /// Returns the number of tokens allowed per interval.
+    pub fn get_limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Returns the length of a window.
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+}
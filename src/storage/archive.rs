@@ -0,0 +1,70 @@
+use crate::storage::{State, Storage};
+
+/// Moves the state of `key` from the `live` storage to the `side` storage,
+/// e.g. to pause the counters of a customer without losing them.
+///
+/// The key starts over with a fresh state in the live storage until
+/// [`restore()`] brings the archived one back. Returns false when the key has
+/// no live state, leaving an earlier archive of the key untouched.
+pub fn archive<A, S: State<A>, Live: Storage<A, S>, Side: Storage<A, S>>(
+    live: &mut Live,
+    side: &mut Side,
+    key: &str,
+) -> bool {
+    let Some(state) = live.fetch(key) else {
+        return false;
+    };
+
+    side.save(key, state);
+    live.delete(key);
+
+    true
+}
+
+/// Moves the state of `key` archived by [`archive()`] back to the `live`
+/// storage, replacing whatever the key counted in the meantime. Returns false
+/// when the key has no archived state.
+pub fn restore<A, S: State<A>, Live: Storage<A, S>, Side: Storage<A, S>>(
+    live: &mut Live,
+    side: &mut Side,
+    key: &str,
+) -> bool {
+    let Some(state) = side.fetch(key) else {
+        return false;
+    };
+
+    live.save(key, state);
+    side.delete(key);
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FixedWindowPolicy, Policy};
+    use crate::storage::InMemoryStorage;
+    use crate::Duration;
+
+    #[test]
+    fn archived_keys_start_over_until_restored() {
+        let (mut live, mut side) = (InMemoryStorage::new(), InMemoryStorage::new());
+        let consume = |live: &mut InMemoryStorage<_, _>, tokens| {
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), live)
+                .unwrap()
+                .consume(tokens)
+                .unwrap()
+                .rate_limit
+                .get_remaining_tokens()
+        };
+
+        assert_eq!(consume(&mut live, 4), 1);
+        assert!(archive(&mut live, &mut side, "key"));
+        assert!(!archive(&mut live, &mut side, "key"));
+        assert_eq!(consume(&mut live, 1), 4);
+
+        assert!(restore(&mut live, &mut side, "key"));
+        assert!(!restore(&mut live, &mut side, "key"));
+        assert_eq!(consume(&mut live, 1), 0);
+    }
+}
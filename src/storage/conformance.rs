@@ -0,0 +1,233 @@
+//! Conformance checks for [`Storage`] implementations.
+//!
+//! Third-party backends can run [`run()`] from their own tests (enable the
+//! `test-util` feature) to verify they honor the contract the policies rely on.
+//! Every violation panics with a message describing the broken expectation.
+
+use crate::storage::{State, Storage};
+use crate::Duration;
+use std::fmt::Debug;
+
+/// Runs every conformance check against `store`. Use an empty store, the checks
+/// write a handful of keys prefixed with `conformance:`.
+///
+/// `state` builds the state stored for a key, expiring after the given duration.
+/// Backends that set a TTL derive it from [`State::get_expiration_time()`], so it
+/// must be kept by a round trip like every other field.
+pub fn run<A, S, Store, F>(mut store: Store, state: F)
+where
+    S: State<A> + PartialEq + Debug,
+    Store: Storage<A, S>,
+    F: Fn(&str, Duration) -> S,
+{
+    fetch_missing_key(&store);
+    save_then_fetch(&mut store, &state);
+    save_overwrites(&mut store, &state);
+    keys_are_isolated(&mut store, &state);
+    delete_removes_key(&mut store, &state);
+    expiration_time_is_kept(&mut store, &state);
+    compare_and_save_checks_current_state(&mut store, &state);
+}
+
+fn fetch_missing_key<A, S: State<A>, Store: Storage<A, S>>(store: &Store) {
+    assert!(
+        store.fetch("conformance:missing").is_none(),
+        "fetch() must return None for a key that was never saved"
+    );
+}
+
+fn save_then_fetch<A, S, Store, F>(store: &mut Store, state: &F)
+where
+    S: State<A> + PartialEq + Debug,
+    Store: Storage<A, S>,
+    F: Fn(&str, Duration) -> S,
+{
+    let saved = state("conformance:saved", Duration::minutes(1));
+    store.save("conformance:saved", saved.clone());
+
+    let fetched = store
+        .fetch("conformance:saved")
+        .expect("fetch() must return a state right after save()");
+    assert_eq!(
+        fetched.get_id(),
+        "conformance:saved",
+        "fetched state has a different key"
+    );
+    assert_eq!(fetched, saved, "fetched state differs from the saved one");
+}
+
+fn save_overwrites<A, S, Store, F>(store: &mut Store, state: &F)
+where
+    S: State<A> + PartialEq + Debug,
+    Store: Storage<A, S>,
+    F: Fn(&str, Duration) -> S,
+{
+    let last = state("conformance:overwrite", Duration::minutes(7));
+    store.save(
+        "conformance:overwrite",
+        state("conformance:overwrite", Duration::minutes(1)),
+    );
+    store.save("conformance:overwrite", last.clone());
+
+    assert_eq!(
+        store.fetch("conformance:overwrite"),
+        Some(last),
+        "save() must replace the previous state"
+    );
+}
+
+fn keys_are_isolated<A, S, Store, F>(store: &mut Store, state: &F)
+where
+    S: State<A> + PartialEq + Debug,
+    Store: Storage<A, S>,
+    F: Fn(&str, Duration) -> S,
+{
+    let a = state("conformance:a", Duration::minutes(1));
+    let b = state("conformance:b", Duration::minutes(2));
+    store.save("conformance:a", a.clone());
+    store.save("conformance:b", b.clone());
+
+    assert_eq!(
+        store.fetch("conformance:a"),
+        Some(a),
+        "keys must not share states"
+    );
+    assert_eq!(
+        store.fetch("conformance:b"),
+        Some(b),
+        "keys must not share states"
+    );
+}
+
+fn delete_removes_key<A, S, Store, F>(store: &mut Store, state: &F)
+where
+    S: State<A> + PartialEq + Debug,
+    Store: Storage<A, S>,
+    F: Fn(&str, Duration) -> S,
+{
+    store.save(
+        "conformance:deleted",
+        state("conformance:deleted", Duration::minutes(1)),
+    );
+    store.save(
+        "conformance:kept",
+        state("conformance:kept", Duration::minutes(1)),
+    );
+    store.delete("conformance:deleted");
+    store.delete("conformance:missing");
+
+    assert!(
+        store.fetch("conformance:deleted").is_none(),
+        "fetch() must return None for a deleted key"
+    );
+    assert!(
+        store.fetch("conformance:kept").is_some(),
+        "delete() must not remove other keys"
+    );
+}
+
+fn expiration_time_is_kept<A, S, Store, F>(store: &mut Store, state: &F)
+where
+    S: State<A> + PartialEq + Debug,
+    Store: Storage<A, S>,
+    F: Fn(&str, Duration) -> S,
+{
+    for (key, ttl) in [
+        ("conformance:ttl:short", Duration::seconds(1)),
+        ("conformance:ttl:long", Duration::days(30)),
+    ] {
+        let saved = state(key, ttl);
+        store.save(key, saved.clone());
+
+        assert_eq!(
+            store
+                .fetch(key)
+                .map(|fetched| fetched.get_expiration_time()),
+            Some(saved.get_expiration_time()),
+            "the expiration time of {key} must survive the round trip"
+        );
+    }
+}
+
+fn compare_and_save_checks_current_state<A, S, Store, F>(store: &mut Store, state: &F)
+where
+    S: State<A> + PartialEq + Debug,
+    Store: Storage<A, S>,
+    F: Fn(&str, Duration) -> S,
+{
+    let key = "conformance:cas";
+    let first = state(key, Duration::minutes(1));
+    let second = state(key, Duration::minutes(2));
+    let stale = state(key, Duration::minutes(3));
+
+    assert!(
+        store.compare_and_save(key, None, first.clone()),
+        "compare_and_save() must save when the key has no state as expected"
+    );
+    assert!(
+        !store.compare_and_save(key, None, stale.clone()),
+        "compare_and_save() must refuse to save when the key has a state, but none was expected"
+    );
+    assert!(
+        !store.compare_and_save(key, Some(&stale), stale.clone()),
+        "compare_and_save() must refuse to save when the current state differs"
+    );
+    assert_eq!(
+        store.fetch(key).as_ref(),
+        Some(&first),
+        "a refused compare_and_save() must keep the current state"
+    );
+    assert!(
+        store.compare_and_save(key, Some(&first), second.clone()),
+        "compare_and_save() must save when the current state is the expected one"
+    );
+    assert_eq!(
+        store.fetch(key),
+        Some(second),
+        "compare_and_save() must replace the state"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::policy::{FixedWindowState, SlidingWindowState};
+    use crate::storage::{InMemoryStorage, StorageRouter, WriteBehindStorage};
+    use crate::{Duration, LocalTime};
+
+    fn fixed_window_state(key: &str, interval: Duration) -> FixedWindowState {
+        FixedWindowState::new(key.into(), &interval, 10)
+    }
+
+    #[test]
+    fn in_memory_storage_conforms() {
+        super::run(InMemoryStorage::new(), fixed_window_state);
+    }
+
+    #[test]
+    fn in_memory_storage_conforms_with_sliding_window_states() {
+        let now = LocalTime::now();
+
+        super::run(InMemoryStorage::new(), |key: &str, interval: Duration| {
+            SlidingWindowState::new(key.into(), &interval, &now)
+        });
+    }
+
+    #[test]
+    fn storage_router_conforms() {
+        super::run(
+            StorageRouter::new(
+                vec![InMemoryStorage::new(), InMemoryStorage::new()],
+                |key: &str| key.len() % 2,
+            ),
+            fixed_window_state,
+        );
+    }
+
+    #[test]
+    fn write_behind_storage_conforms() {
+        super::run(
+            WriteBehindStorage::new(InMemoryStorage::new(), 2, Duration::hours(1)),
+            fixed_window_state,
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::storage::{State, Storage};
+use crate::sync::{HashMap, Mutex};
+use std::marker::PhantomData;
+
+pub struct InMemoryStorage<A: Sized, S: State<A>> {
+    store: HashMap<String, Mutex<S>>,
+    _phantom_data: PhantomData<A>,
+}
+
+impl<A: Sized, S: State<A>> InMemoryStorage<A, S> {
+    pub fn new() -> Self {
+        Self {
+            store: HashMap::new(),
+            _phantom_data: Default::default(),
+        }
+    }
+
+    /// Keeps only the keys for which `keep` returns true, e.g. to drop keys idle
+    /// for too long based on [`State::get_last_hit_at()`].
+    pub fn retain<F: FnMut(&str, &S) -> bool>(&mut self, mut keep: F) {
+        self.store.retain(|key, state| keep(key, state.get_mut()));
+    }
+
+    /// Copies the state of `key` from `storage` into a new in-memory storage, e.g. to
+    /// try requests out on it without touching the original.
+    pub fn fork<Store: Storage<A, S>>(storage: &Store, key: &str) -> Self {
+        let mut fork = Self::new();
+
+        if let Some(state) = storage.fetch(key) {
+            fork.save(key, state);
+        }
+
+        fork
+    }
+
+    /// Returns the number of stored keys.
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Returns true when no key is stored.
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+}
+
+impl<A: Sized, S: State<A>> Default for InMemoryStorage<A, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Sized, S: State<A>> Storage<A, S> for InMemoryStorage<A, S> {
+    fn fetch(&self, key: &str) -> Option<S> {
+        if let Some(value) = self.store.get(key) {
+            return Some(value.lock().clone());
+        }
+
+        None
+    }
+
+    fn save<IntoString: Into<String>>(&mut self, key: IntoString, value: S) {
+        let key = key.into();
+
+        match self.store.get_mut(&key) {
+            Some(state) => *state.get_mut() = value,
+            None => {
+                self.store.insert(key, Mutex::new(value));
+            }
+        }
+    }
+
+    fn delete(&mut self, key: &str) {
+        self.store.remove(key);
+    }
+}
@@ -0,0 +1,57 @@
+mod archive;
+#[cfg(any(test, feature = "test-util"))]
+pub mod conformance;
+mod in_memory;
+mod read_only;
+mod router;
+mod write_behind;
+
+use crate::LocalDateTime;
+
+pub use archive::{archive, restore};
+pub use in_memory::InMemoryStorage;
+pub use read_only::ReadOnlyStorage;
+pub use router::StorageRouter;
+pub use write_behind::WriteBehindStorage;
+
+pub trait Storage<Inner, S: State<Inner>> {
+    fn fetch(&self, key: &str) -> Option<S>;
+
+    fn save<IntoString: Into<String>>(&mut self, key: IntoString, value: S);
+
+    /// Removes the state of `key`, if any.
+    fn delete(&mut self, key: &str);
+
+    /// Saves `value` only if the state of `key` is still `expected`, None meaning
+    /// that the key has no state. Returns whether `value` was saved.
+    ///
+    /// Shared backends should override this with an atomic operation, the default
+    /// fetches and saves separately.
+    fn compare_and_save(&mut self, key: &str, expected: Option<&S>, value: S) -> bool
+    where
+        S: PartialEq,
+    {
+        if self.fetch(key).as_ref() != expected {
+            return false;
+        }
+
+        self.save(key, value);
+        true
+    }
+}
+
+pub trait State<Body>: Clone {
+    fn get_id(&self) -> String;
+
+    fn get_expiration_time(&self) -> u64;
+
+    /// Returns when the key was hit for the first time, if the state tracks it.
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        None
+    }
+
+    /// Returns when the key was hit for the last time, if the state tracks it.
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        None
+    }
+}
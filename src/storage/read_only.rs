@@ -0,0 +1,60 @@
+use crate::storage::{State, Storage};
+
+/// A view over another storage that never writes to it.
+///
+/// Policies built on top of it can check and inspect states from the shared
+/// storage, but whatever they consume is discarded instead of being saved,
+/// which makes it safe to hand to analytics or reporting code.
+pub struct ReadOnlyStorage<'a, Store> {
+    storage: &'a Store,
+}
+
+impl<'a, Store> ReadOnlyStorage<'a, Store> {
+    pub fn new(storage: &'a Store) -> Self {
+        Self { storage }
+    }
+}
+
+impl<A, S: State<A>, Store: Storage<A, S>> Storage<A, S> for ReadOnlyStorage<'_, Store> {
+    fn fetch(&self, key: &str) -> Option<S> {
+        self.storage.fetch(key)
+    }
+
+    fn save<IntoString: Into<String>>(&mut self, _key: IntoString, _value: S) {}
+
+    fn delete(&mut self, _key: &str) {}
+
+    fn compare_and_save(&mut self, _key: &str, _expected: Option<&S>, _value: S) -> bool
+    where
+        S: PartialEq,
+    {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FixedWindowPolicy, FixedWindowState, Policy};
+    use crate::storage::InMemoryStorage;
+    use crate::Duration;
+
+    #[test]
+    fn read_only_storage_discards_saves() {
+        let mut storage = InMemoryStorage::new();
+        FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut storage)
+            .unwrap()
+            .consume(2)
+            .unwrap();
+
+        let mut read_only = ReadOnlyStorage::new(&storage);
+        let mut mirror =
+            FixedWindowPolicy::new(5, "key".into(), Duration::minutes(1), &mut read_only).unwrap();
+
+        assert_eq!(mirror.peek().unwrap().get_remaining_tokens(), 3);
+        mirror.consume(3).unwrap();
+
+        let state: FixedWindowState = storage.fetch("key").unwrap();
+        assert_eq!(state.hit_count, 2);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::storage::{State, Storage};
+use std::marker::PhantomData;
+
+/// Sends every key to one of several backends picked by a routing function,
+/// e.g. EU tenants to an EU store and US tenants to a US store.
+///
+/// The routing function maps a key to the index of its backend and must
+/// always return the same index for the same key, otherwise the key's state
+/// is split across backends. States never move between backends.
+///
+/// # Panics
+///
+/// [`Storage::fetch()`], [`Storage::save()`] and [`Storage::delete()`] panic when the routing function
+/// returns an index without a backend, rather than writing the state anywhere else.
+pub struct StorageRouter<A, S: State<A>, Store: Storage<A, S>, F: Fn(&str) -> usize> {
+    backends: Vec<Store>,
+    route: F,
+    _phantom_data: PhantomData<(A, S)>,
+}
+
+impl<A, S: State<A>, Store: Storage<A, S>, F: Fn(&str) -> usize> StorageRouter<A, S, Store, F> {
+    pub fn new(backends: Vec<Store>, route: F) -> Self {
+        Self {
+            backends,
+            route,
+            _phantom_data: Default::default(),
+        }
+    }
+
+    /// Returns the backends, in the order the routing function indexes them.
+    pub fn get_ref(&self) -> &[Store] {
+        &self.backends
+    }
+
+    pub fn into_inner(self) -> Vec<Store> {
+        self.backends
+    }
+
+    fn backend_index(&self, key: &str) -> usize {
+        let index = (self.route)(key);
+
+        assert!(
+            index < self.backends.len(),
+            "key {key:?} was routed to backend {index}, but there are only {} backends",
+            self.backends.len()
+        );
+
+        index
+    }
+}
+
+impl<A, S: State<A>, Store: Storage<A, S>, F: Fn(&str) -> usize> Storage<A, S>
+    for StorageRouter<A, S, Store, F>
+{
+    fn fetch(&self, key: &str) -> Option<S> {
+        self.backends[self.backend_index(key)].fetch(key)
+    }
+
+    fn save<IntoString: Into<String>>(&mut self, key: IntoString, value: S) {
+        let key = key.into();
+        let index = self.backend_index(&key);
+
+        self.backends[index].save(key, value);
+    }
+
+    fn delete(&mut self, key: &str) {
+        let index = self.backend_index(key);
+
+        self.backends[index].delete(key);
+    }
+
+    fn compare_and_save(&mut self, key: &str, expected: Option<&S>, value: S) -> bool
+    where
+        S: PartialEq,
+    {
+        let index = self.backend_index(key);
+
+        self.backends[index].compare_and_save(key, expected, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{FixedWindowPolicy, Policy};
+    use crate::storage::InMemoryStorage;
+    use crate::Duration;
+
+    #[test]
+    fn routes_keys_to_their_backend() {
+        let mut router = StorageRouter::new(
+            vec![InMemoryStorage::new(), InMemoryStorage::new()],
+            |key: &str| usize::from(key.starts_with("us:")),
+        );
+
+        for key in ["eu:alice", "us:bob"] {
+            FixedWindowPolicy::new(5, key.into(), Duration::minutes(1), &mut router)
+                .unwrap()
+                .consume(1)
+                .unwrap();
+        }
+
+        let (eu, us) = (&router.get_ref()[0], &router.get_ref()[1]);
+        assert!(eu.fetch("eu:alice").is_some() && eu.fetch("us:bob").is_none());
+        assert!(us.fetch("us:bob").is_some() && us.fetch("eu:alice").is_none());
+    }
+}
@@ -0,0 +1,145 @@
+use crate::storage::{State, Storage};
+use crate::sync::HashMap;
+use crate::{ChronoTimestampMillis, Clock, Duration, SystemClock};
+use std::marker::PhantomData;
+
+/// Buffers saves in memory and writes them to the wrapped storage in batches.
+///
+/// Pending states are flushed once `max_updates` saves have been buffered or
+/// `flush_interval` has passed since the last flush, whichever comes first.
+/// The check happens on every save; there is no background timer, so call
+/// [`Self::flush()`] from your own scheduler if the key traffic can stop.
+///
+/// Fetches see buffered states immediately, so a single process never reads
+/// stale data. Other processes sharing the wrapped storage may lag behind by
+/// at most one flush. If the process dies, at most `max_updates` saves (or
+/// `flush_interval` worth of updates) are lost; the buffer is flushed on drop.
+pub struct WriteBehindStorage<A, S: State<A>, Store: Storage<A, S>> {
+    storage: Store,
+    pending: HashMap<String, S>,
+    updates: usize,
+    max_updates: usize,
+    flush_interval: ChronoTimestampMillis,
+    last_flush_at: ChronoTimestampMillis,
+    clock: Box<dyn Clock>,
+    _phantom_data: PhantomData<A>,
+}
+
+impl<A, S: State<A>, Store: Storage<A, S>> WriteBehindStorage<A, S, Store> {
+    pub fn new(storage: Store, max_updates: usize, flush_interval: Duration) -> Self {
+        Self {
+            storage,
+            pending: HashMap::new(),
+            updates: 0,
+            max_updates,
+            flush_interval: flush_interval.num_milliseconds(),
+            last_flush_at: SystemClock.now().timestamp_millis(),
+            clock: Box::new(SystemClock),
+            _phantom_data: Default::default(),
+        }
+    }
+
+    /// Replaces the clock the flush interval is measured with.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.last_flush_at = clock.now().timestamp_millis();
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Writes all buffered states to the wrapped storage.
+    pub fn flush(&mut self) {
+        for (key, value) in self.pending.drain() {
+            self.storage.save(key, value);
+        }
+
+        self.updates = 0;
+        self.last_flush_at = self.clock.now().timestamp_millis();
+    }
+
+    /// Returns the number of keys waiting to be flushed.
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the wrapped storage. Buffered states are not visible through it until flushed.
+    pub fn get_ref(&self) -> &Store {
+        &self.storage
+    }
+}
+
+impl<A, S: State<A>, Store: Storage<A, S>> Storage<A, S> for WriteBehindStorage<A, S, Store> {
+    fn fetch(&self, key: &str) -> Option<S> {
+        if let Some(value) = self.pending.get(key) {
+            return Some(value.clone());
+        }
+
+        self.storage.fetch(key)
+    }
+
+    fn save<IntoString: Into<String>>(&mut self, key: IntoString, value: S) {
+        self.pending.insert(key.into(), value);
+        self.updates += 1;
+
+        let elapsed = self.clock.now().timestamp_millis() - self.last_flush_at;
+        if self.updates >= self.max_updates || elapsed >= self.flush_interval {
+            self.flush();
+        }
+    }
+
+    /// Deletes the key from the wrapped storage right away, dropping any buffered state.
+    fn delete(&mut self, key: &str) {
+        self.pending.remove(key);
+        self.storage.delete(key);
+    }
+}
+
+impl<A, S: State<A>, Store: Storage<A, S>> Drop for WriteBehindStorage<A, S, Store> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::FixedWindowState;
+    use crate::storage::InMemoryStorage;
+    use crate::MockClock;
+
+    #[test]
+    fn flushes_after_max_updates() {
+        let mut storage = WriteBehindStorage::new(InMemoryStorage::new(), 2, Duration::hours(1));
+        let state = FixedWindowState::new("a".into(), &Duration::minutes(1), 10);
+
+        storage.save("a", state.clone());
+        assert_eq!(storage.pending_len(), 1);
+        assert!(storage.fetch("a").is_some());
+        assert!(storage.get_ref().fetch("a").is_none());
+
+        storage.save("b", state);
+        assert_eq!(storage.pending_len(), 0);
+        assert!(storage.get_ref().fetch("a").is_some());
+        assert!(storage.get_ref().fetch("b").is_some());
+    }
+
+    #[test]
+    fn flushes_after_the_flush_interval() {
+        let clock = MockClock::default();
+        let mut storage =
+            WriteBehindStorage::new(InMemoryStorage::new(), 100, Duration::seconds(5))
+                .with_clock(clock.clone());
+        let state = FixedWindowState::new("a".into(), &Duration::minutes(1), 10);
+
+        storage.save("a", state.clone());
+        clock.advance(Duration::seconds(4));
+        storage.save("b", state.clone());
+        assert_eq!(storage.pending_len(), 2);
+        assert!(storage.get_ref().fetch("a").is_none());
+
+        clock.advance(Duration::seconds(1));
+        storage.save("c", state);
+        assert_eq!(storage.pending_len(), 0);
+        assert!(storage.get_ref().fetch("a").is_some());
+        assert!(storage.get_ref().fetch("c").is_some());
+    }
+}
@@ -0,0 +1,47 @@
+//! Synchronization primitives used across the crate.
+//!
+//! The default `fast-sync` feature uses `parking_lot` and `hashbrown`. Without it,
+//! or with the `std-sync` feature, the crate falls back to the standard library
+//! and drops both dependencies. The API is the same either way: locking never
+//! fails, a poisoned std mutex is recovered.
+
+#[cfg(all(feature = "fast-sync", not(feature = "std-sync")))]
+pub(crate) use hashbrown::HashMap;
+#[cfg(all(feature = "fast-sync", not(feature = "std-sync")))]
+pub use parking_lot::{Mutex, MutexGuard};
+
+#[cfg(any(not(feature = "fast-sync"), feature = "std-sync"))]
+pub use self::std_mutex::{Mutex, MutexGuard};
+#[cfg(any(not(feature = "fast-sync"), feature = "std-sync"))]
+pub(crate) use std::collections::HashMap;
+
+#[cfg(any(not(feature = "fast-sync"), feature = "std-sync"))]
+mod std_mutex {
+    use std::sync::PoisonError;
+
+    pub type MutexGuard<'a, T> = std::sync::MutexGuard<'a, T>;
+
+    /// A [`std::sync::Mutex`] with the infallible API of `parking_lot::Mutex`.
+    #[derive(Debug, Default)]
+    pub struct Mutex<T: ?Sized>(std::sync::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub fn new(value: T) -> Self {
+            Self(std::sync::Mutex::new(value))
+        }
+
+        pub fn into_inner(self) -> T {
+            self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    impl<T: ?Sized> Mutex<T> {
+        pub fn lock(&self) -> MutexGuard<'_, T> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::error::{LimiterError, PolicyError};
+use crate::policy::{FixedWindowPolicy, FixedWindowState, Policy};
+use crate::storage::{InMemoryStorage, State, Storage};
+use crate::{Clock, Duration, LocalDateTime, RateLimit, SystemClock, Unit};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+struct NamedQuota {
+    name: String,
+    limit: u64,
+    interval: Duration,
+}
+
+/// Several named fixed window limits per tenant, e.g. 1,000 `api_calls` and 10
+/// `exports` per hour, behind a single [`Self::consume()`].
+///
+/// All the windows of a tenant live in one [`TenantState`] under the tenant's
+/// key, so a consume is one fetch and one save whatever the number of quotas.
+pub struct TenantQuotas<'a, Store: Storage<TenantState, TenantState>> {
+    quotas: Vec<NamedQuota>,
+    storage: &'a mut Store,
+    unit: Unit,
+    clock: Arc<dyn Clock>,
+}
+
+impl<'a, Store: Storage<TenantState, TenantState>> TenantQuotas<'a, Store> {
+    pub fn new(storage: &'a mut Store) -> Self {
+        Self {
+            quotas: Vec::new(),
+            storage,
+            unit: Unit::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Adds a quota allowing every tenant `limit` tokens of `name` per `interval`.
+    /// A quota added again under the same name replaces the previous one.
+    pub fn with_quota<S: Into<String>>(
+        mut self,
+        name: S,
+        limit: u64,
+        interval: Duration,
+    ) -> Result<Self, PolicyError> {
+        let name = name.into();
+
+        if limit == 0 {
+            return Err(PolicyError::ZeroLimitError);
+        }
+
+        if name.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        self.quotas.retain(|quota| quota.name != name);
+        self.quotas.push(NamedQuota {
+            name,
+            limit,
+            interval,
+        });
+
+        Ok(self)
+    }
+
+    /// Sets the unit reported by the rate limits of the quotas.
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Replaces the clock the quotas read the current time from.
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Consumes `cost` tokens of the quota `quota_name` for `tenant`, if they
+    /// are available right now.
+    pub fn consume(
+        &mut self,
+        tenant: &str,
+        quota_name: &str,
+        cost: u64,
+    ) -> Result<RateLimit, LimiterError> {
+        let quota = self.get_quota(tenant, quota_name)?;
+        let mut state = self
+            .storage
+            .fetch(tenant)
+            .unwrap_or_else(|| TenantState::new(tenant.to_string()));
+        let mut window = InMemoryStorage::new();
+
+        if let Some(quota_state) = state.quotas.remove(quota_name) {
+            window.save(quota_name, quota_state);
+        }
+
+        let rate_limit = self.policy(quota, &mut window).consume(cost)?.rate_limit;
+
+        if let Some(quota_state) = window.fetch(quota_name) {
+            state.quotas.insert(quota_name.to_string(), quota_state);
+        }
+
+        self.storage.save(tenant, state);
+
+        Ok(rate_limit)
+    }
+
+    /// Returns the rate limit of every quota of `tenant`, in the order the
+    /// quotas were added, without recording anything.
+    pub fn peek(&self, tenant: &str) -> Result<Vec<(&str, RateLimit)>, LimiterError> {
+        if tenant.is_empty() {
+            return Err(PolicyError::EmptyKeyError.into());
+        }
+
+        let state = self.storage.fetch(tenant);
+
+        self.quotas
+            .iter()
+            .map(|quota| {
+                let mut window = InMemoryStorage::new();
+
+                if let Some(quota_state) = state
+                    .as_ref()
+                    .and_then(|state| state.quotas.get(&quota.name))
+                {
+                    window.save(quota.name.as_str(), quota_state.clone());
+                }
+
+                Ok((quota.name.as_str(), self.policy(quota, &mut window).peek()?))
+            })
+            .collect()
+    }
+
+    fn get_quota(&self, tenant: &str, quota_name: &str) -> Result<&NamedQuota, PolicyError> {
+        if tenant.is_empty() {
+            return Err(PolicyError::EmptyKeyError);
+        }
+
+        self.quotas
+            .iter()
+            .find(|quota| quota.name == quota_name)
+            .ok_or_else(|| PolicyError::UnknownQuotaError(quota_name.to_string()))
+    }
+
+    fn policy<'w>(
+        &self,
+        quota: &NamedQuota,
+        window: &'w mut InMemoryStorage<FixedWindowState, FixedWindowState>,
+    ) -> FixedWindowPolicy<'w, InMemoryStorage<FixedWindowState, FixedWindowState>> {
+        FixedWindowPolicy::new(quota.limit, quota.name.clone(), quota.interval, window)
+            .expect("the limit and the name are validated by TenantQuotas::with_quota")
+            .with_unit(self.unit.clone())
+            .with_clock(Arc::clone(&self.clock))
+    }
+}
+
+/// The windows of all the quotas of a tenant, by quota name.
+#[derive(Debug, Clone)]
+pub struct TenantState {
+    pub key: String,
+    pub quotas: BTreeMap<String, FixedWindowState>,
+}
+
+impl State<TenantState> for TenantState {
+    fn get_id(&self) -> String {
+        self.key.clone()
+    }
+
+    fn get_expiration_time(&self) -> u64 {
+        self.quotas
+            .values()
+            .map(|quota_state| quota_state.get_expiration_time())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn get_created_at(&self) -> Option<LocalDateTime> {
+        self.quotas
+            .values()
+            .filter_map(|quota_state| quota_state.get_created_at())
+            .min()
+    }
+
+    fn get_last_hit_at(&self) -> Option<LocalDateTime> {
+        self.quotas
+            .values()
+            .filter_map(|quota_state| quota_state.get_last_hit_at())
+            .max()
+    }
+}
+
+impl TenantState {
+    pub fn new(key: String) -> Self {
+        Self {
+            key,
+            quotas: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ReserveError;
+
+    #[test]
+    fn quotas_are_counted_separately_in_one_record() {
+        let mut storage = InMemoryStorage::new();
+        let mut quotas = TenantQuotas::new(&mut storage)
+            .with_quota("api_calls", 100, Duration::hours(1))
+            .unwrap()
+            .with_quota("exports", 2, Duration::hours(1))
+            .unwrap();
+
+        assert!(quotas.consume("acme", "exports", 2).unwrap().is_accepted());
+        assert!(!quotas.consume("acme", "exports", 1).unwrap().is_accepted());
+        assert!(quotas
+            .consume("acme", "api_calls", 10)
+            .unwrap()
+            .is_accepted());
+        assert!(matches!(
+            quotas.consume("acme", "exports", 3),
+            Err(LimiterError::ReserveError(
+                ReserveError::TooManyTokensError { .. }
+            ))
+        ));
+        assert!(matches!(
+            quotas.consume("acme", "webhooks", 1),
+            Err(LimiterError::PolicyError(PolicyError::UnknownQuotaError(_)))
+        ));
+
+        let usage = quotas.peek("acme").unwrap();
+        assert_eq!(usage[0].0, "api_calls");
+        assert_eq!(usage[0].1.get_remaining_tokens(), 90);
+        assert_eq!(usage[1].1.get_remaining_tokens(), 0);
+
+        assert_eq!(storage.fetch("acme").unwrap().quotas.len(), 2);
+    }
+}
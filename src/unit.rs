@@ -0,0 +1,26 @@
+use std::fmt::{Display, Formatter};
+
+/// What a single token of a policy stands for.
+///
+/// The unit does not change how limits are computed, it is carried through
+/// [`crate::RateLimit`] so that callers can report e.g. "bytes remaining"
+/// instead of anonymous tokens.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Unit {
+    #[default]
+    Requests,
+    Bytes,
+    Credits,
+    Custom(String),
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Requests => f.write_str("requests"),
+            Unit::Bytes => f.write_str("bytes"),
+            Unit::Credits => f.write_str("credits"),
+            Unit::Custom(name) => f.write_str(name),
+        }
+    }
+}